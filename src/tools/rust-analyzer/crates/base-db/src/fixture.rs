@@ -301,7 +301,7 @@ pub fn parse_with_proc_macros(
     }
 }
 
-fn default_test_proc_macros() -> [(String, ProcMacro); 4] {
+fn default_test_proc_macros() -> [(String, ProcMacro); 7] {
     [
         (
             r#"
@@ -359,6 +359,48 @@ pub fn mirror(input: TokenStream) -> TokenStream {
                 expander: Arc::new(MirrorProcMacroExpander),
             },
         ),
+        (
+            r#"
+#[proc_macro]
+pub fn spanned_fn(input: TokenStream) -> TokenStream {
+    input
+}
+"#
+            .into(),
+            ProcMacro {
+                name: "spanned_fn".into(),
+                kind: crate::ProcMacroKind::FuncLike,
+                expander: Arc::new(SpannedFnProcMacroExpander),
+            },
+        ),
+        (
+            r#"
+#[proc_macro_derive(DeriveExpr)]
+pub fn derive_expr(_item: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
+"#
+            .into(),
+            ProcMacro {
+                name: "DeriveExpr".into(),
+                kind: crate::ProcMacroKind::CustomDerive,
+                expander: Arc::new(ExprProcMacroExpander),
+            },
+        ),
+        (
+            r#"
+#[proc_macro_derive(DeriveWithHelper, attributes(helper))]
+pub fn derive_with_helper(_item: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
+"#
+            .into(),
+            ProcMacro {
+                name: "DeriveWithHelper".into(),
+                kind: crate::ProcMacroKind::CustomDerive,
+                expander: Arc::new(EmptyProcMacroExpander),
+            },
+        ),
     ]
 }
 
@@ -500,3 +542,78 @@ fn traverse(input: &Subtree) -> Subtree {
         Ok(traverse(input))
     }
 }
+
+// Emits `fn $ident() {}` for the first identifier of its input. Like `quote!`, only the
+// interpolated identifier keeps its span, all the other tokens are unspanned.
+#[derive(Debug)]
+struct SpannedFnProcMacroExpander;
+impl ProcMacroExpander for SpannedFnProcMacroExpander {
+    fn expand(
+        &self,
+        input: &Subtree,
+        _: Option<&Subtree>,
+        _: &Env,
+    ) -> Result<Subtree, ProcMacroExpansionError> {
+        let name = input
+            .token_trees
+            .iter()
+            .find_map(|tt| match tt {
+                tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => Some(ident.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| ProcMacroExpansionError::Panic("Expected an identifier".into()))?;
+        let group = |kind| {
+            tt::TokenTree::Subtree(Subtree {
+                delimiter: Some(tt::Delimiter { id: tt::TokenId::unspecified(), kind }),
+                token_trees: Vec::new(),
+            })
+        };
+        let fn_ = tt::Ident { text: "fn".into(), id: tt::TokenId::unspecified() };
+        Ok(Subtree {
+            delimiter: None,
+            token_trees: vec![
+                tt::Leaf::Ident(fn_).into(),
+                tt::Leaf::Ident(name).into(),
+                group(tt::DelimiterKind::Parenthesis),
+                group(tt::DelimiterKind::Brace),
+            ],
+        })
+    }
+}
+
+// Emits `1 + 1`, which is not a valid derive output
+#[derive(Debug)]
+struct ExprProcMacroExpander;
+impl ProcMacroExpander for ExprProcMacroExpander {
+    fn expand(
+        &self,
+        _: &Subtree,
+        _: Option<&Subtree>,
+        _: &Env,
+    ) -> Result<Subtree, ProcMacroExpansionError> {
+        let one = || {
+            let one = tt::Literal { text: "1".into(), id: tt::TokenId::unspecified() };
+            tt::TokenTree::Leaf(one.into())
+        };
+        let plus =
+            tt::Punct { char: '+', spacing: tt::Spacing::Alone, id: tt::TokenId::unspecified() };
+        Ok(Subtree {
+            delimiter: None,
+            token_trees: vec![one(), tt::Leaf::Punct(plus).into(), one()],
+        })
+    }
+}
+
+// Emits nothing, like a derive only implementing a marker trait elsewhere
+#[derive(Debug)]
+struct EmptyProcMacroExpander;
+impl ProcMacroExpander for EmptyProcMacroExpander {
+    fn expand(
+        &self,
+        _: &Subtree,
+        _: Option<&Subtree>,
+        _: &Env,
+    ) -> Result<Subtree, ProcMacroExpansionError> {
+        Ok(Subtree::default())
+    }
+}
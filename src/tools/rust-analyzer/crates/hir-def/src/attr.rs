@@ -1,18 +1,25 @@
 //! A higher level attributes based on TokenTree, with also some shortcuts.
 
+#[cfg(test)]
+mod tests;
+
 use std::{fmt, hash::Hash, ops, sync::Arc};
 
 use base_db::CrateId;
 use cfg::{CfgExpr, CfgOptions};
 use either::Either;
-use hir_expand::{hygiene::Hygiene, name::AsName, HirFileId, InFile};
+use hir_expand::{
+    hygiene::Hygiene,
+    name::{attrs, AsName},
+    HirFileId, InFile,
+};
 use itertools::Itertools;
 use la_arena::{ArenaMap, Idx, RawIdx};
 use mbe::{syntax_node_to_token_tree, DelimiterKind, Punct};
 use smallvec::{smallvec, SmallVec};
 use syntax::{
     ast::{self, AstNode, HasAttrs, IsString},
-    match_ast, AstPtr, AstToken, SmolStr, SyntaxNode, TextRange, TextSize,
+    match_ast, AstPtr, AstToken, SmolStr, SyntaxKind, SyntaxNode, TextRange, TextSize,
 };
 use tt::Subtree;
 
@@ -148,9 +155,8 @@ pub(crate) fn merge(&self, other: Self) -> Self {
 
     /// Processes `cfg_attr`s, returning the resulting semantic `Attrs`.
     pub(crate) fn filter(self, db: &dyn DefDatabase, krate: CrateId) -> Attrs {
-        let has_cfg_attrs = self.iter().any(|attr| {
-            attr.path.as_ident().map_or(false, |name| *name == hir_expand::name![cfg_attr])
-        });
+        let has_cfg_attrs =
+            self.iter().any(|attr| attr.path.as_ident().map_or(false, |name| name.is_cfg_attr()));
         if !has_cfg_attrs {
             return Attrs(self);
         }
@@ -159,8 +165,7 @@ pub(crate) fn filter(self, db: &dyn DefDatabase, krate: CrateId) -> Attrs {
         let new_attrs = self
             .iter()
             .flat_map(|attr| -> SmallVec<[_; 1]> {
-                let is_cfg_attr =
-                    attr.path.as_ident().map_or(false, |name| *name == hir_expand::name![cfg_attr]);
+                let is_cfg_attr = attr.path.as_ident().map_or(false, |name| name.is_cfg_attr());
                 if !is_cfg_attr {
                     return smallvec![attr.clone()];
                 }
@@ -308,7 +313,7 @@ pub fn by_key(&self, key: &'static str) -> AttrQuery<'_> {
 
 impl Attrs {
     pub fn cfg(&self) -> Option<CfgExpr> {
-        let mut cfgs = self.by_key("cfg").tt_values().map(CfgExpr::parse);
+        let mut cfgs = self.by_key(attrs::CFG).tt_values().map(CfgExpr::parse);
         let first = cfgs.next()?;
         match cfgs.next() {
             Some(second) => {
@@ -330,7 +335,7 @@ pub fn lang(&self) -> Option<&SmolStr> {
     }
 
     pub fn docs(&self) -> Option<Documentation> {
-        let docs = self.by_key("doc").attrs().filter_map(|attr| attr.string_value());
+        let docs = self.by_key(attrs::DOC).attrs().filter_map(|attr| attr.string_value());
         let indent = doc_indent(self);
         let mut buf = String::new();
         for doc in docs {
@@ -357,9 +362,9 @@ pub fn docs(&self) -> Option<Documentation> {
     }
 
     pub fn has_doc_hidden(&self) -> bool {
-        self.by_key("doc").tt_values().any(|tt| {
+        self.by_key(attrs::DOC).tt_values().any(|tt| {
             tt.delimiter_kind() == Some(DelimiterKind::Parenthesis) &&
-                matches!(&*tt.token_trees, [tt::TokenTree::Leaf(tt::Leaf::Ident(ident))] if ident.text == "hidden")
+                matches!(&*tt.token_trees, [tt::TokenTree::Leaf(tt::Leaf::Ident(ident))] if ident.text == attrs::HIDDEN)
         })
     }
 
@@ -574,8 +579,10 @@ pub fn docs_with_rangemap(
         &self,
         db: &dyn DefDatabase,
     ) -> Option<(Documentation, DocsRangeMap)> {
-        let docs =
-            self.by_key("doc").attrs().filter_map(|attr| attr.string_value().map(|s| (s, attr.id)));
+        let docs = self
+            .by_key(attrs::DOC)
+            .attrs()
+            .filter_map(|attr| attr.string_value().map(|s| (s, attr.id)));
         let indent = doc_indent(self);
         let mut buf = String::new();
         let mut mapping = Vec::new();
@@ -617,7 +624,7 @@ pub fn docs_with_rangemap(
 
 fn doc_indent(attrs: &Attrs) -> usize {
     attrs
-        .by_key("doc")
+        .by_key(attrs::DOC)
         .attrs()
         .filter_map(|attr| attr.string_value())
         .flat_map(|s| s.lines())
@@ -761,6 +768,32 @@ pub fn map(&self, range: TextRange) -> Option<InFile<TextRange>> {
             }
         }
     }
+
+    /// Like [`DocsRangeMap::map`], but for docs of macro generated items, the range is also mapped
+    /// up out of the expansion, into the file the docs were actually written in. For items
+    /// generated from a documented template in a `macro_rules!` arm, that's the macro definition.
+    pub fn map_up(&self, db: &dyn DefDatabase, range: TextRange) -> Option<InFile<TextRange>> {
+        let mut mapped = self.map(range)?;
+        while mapped.file_id.is_macro() {
+            let root = db.parse_or_expand(mapped.file_id)?;
+            let token = root.covering_element(mapped.value).into_token()?;
+            let relative_range = mapped.value - token.text_range().start();
+            let up = InFile::new(mapped.file_id, token.clone()).upmap(db.upcast())?;
+            let relative_range = match (token.kind(), up.value.kind()) {
+                // Doc comments are turned into `#[doc = "..."]` attributes when lowered to token
+                // trees, so the doc string of the expansion maps back to a doc comment.
+                (SyntaxKind::STRING, SyntaxKind::COMMENT) => {
+                    let open_quote = ast::String::cast(token)?.open_quote_text_range()?.len();
+                    let prefix = ast::Comment::cast(up.value.clone())?.prefix().len();
+                    relative_range.checked_sub(open_quote)? + TextSize::try_from(prefix).ok()?
+                }
+                (expanded, original) if expanded == original => relative_range,
+                _ => return None,
+            };
+            mapped = InFile::new(up.file_id, relative_range + up.value.text_range().start());
+        }
+        Some(mapped)
+    }
 }
 
 fn get_doc_string_in_attr(it: &ast::Attr) -> Option<ast::String> {
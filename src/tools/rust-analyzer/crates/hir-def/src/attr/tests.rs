@@ -0,0 +1,42 @@
+//! Tests for the documentation of items, as computed from their attributes.
+
+use base_db::{fixture::WithFixture, SourceDatabaseExt};
+use syntax::{TextRange, TextSize};
+
+use crate::{db::DefDatabase, test_db::TestDB, ModuleDefId};
+
+#[test]
+fn docs_of_macro_generated_item_map_up_to_macro_definition() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+macro_rules! m {
+    () => {
+        /// Docs of `f`
+        fn f() {}
+    };
+}
+m!();
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let def_map = module.def_map(&db);
+    let f = def_map[module.local_id]
+        .scope
+        .declarations()
+        .find_map(|it| match it {
+            ModuleDefId::FunctionId(it) => Some(it),
+            _ => None,
+        })
+        .unwrap();
+
+    let (docs, mapping) = db.attrs(f.into()).docs_with_rangemap(&db).unwrap();
+    assert_eq!(docs.as_str(), "Docs of `f`");
+
+    let offset = docs.as_str().find("`f`").unwrap();
+    let range = TextRange::at(TextSize::try_from(offset).unwrap(), TextSize::of("`f`"));
+    assert!(mapping.map(range).unwrap().file_id.is_macro());
+
+    let mapped = mapping.map_up(&db, range).unwrap();
+    assert_eq!(mapped.file_id, file_id.into());
+    assert_eq!(&db.file_text(file_id)[mapped.value], "`f`");
+}
@@ -294,7 +294,7 @@ pub struct BodySourceMap {
 #[derive(Debug, Eq, PartialEq)]
 pub enum BodyDiagnostic {
     InactiveCode { node: InFile<SyntaxNodePtr>, cfg: CfgExpr, opts: CfgOptions },
-    MacroError { node: InFile<AstPtr<ast::MacroCall>>, message: String },
+    MacroError { node: InFile<AstPtr<ast::MacroCall>>, message: String, is_compile_error: bool },
     UnresolvedProcMacro { node: InFile<AstPtr<ast::MacroCall>>, krate: CrateId },
     UnresolvedMacroCall { node: InFile<AstPtr<ast::MacroCall>>, path: ModPath },
 }
@@ -457,6 +457,14 @@ pub fn node_macro_file(&self, node: InFile<&ast::MacroCall>) -> Option<HirFileId
         self.expansions.get(&src).cloned()
     }
 
+    /// Returns the macro calls expanded while lowering the body, with the files of their
+    /// expansions.
+    pub fn macro_calls(
+        &self,
+    ) -> impl Iterator<Item = (InFile<AstPtr<ast::MacroCall>>, HirFileId)> + '_ {
+        self.expansions.iter().map(|(call, &file)| (call.clone(), file))
+    }
+
     pub fn pat_syntax(&self, pat: PatId) -> Result<PatSource, SyntheticSyntax> {
         self.pat_map_back.get(pat).cloned().ok_or(SyntheticSyntax)
     }
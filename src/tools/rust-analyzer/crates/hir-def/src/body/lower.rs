@@ -345,13 +345,13 @@ fn maybe_collect_expr(&mut self, expr: ast::Expr) -> Option<ExprId> {
                 self.alloc_expr(path, syntax_ptr)
             }
             ast::Expr::ContinueExpr(e) => self.alloc_expr(
-                Expr::Continue { label: e.lifetime().map(|l| Name::new_lifetime(&l)) },
+                Expr::Continue { label: e.lifetime().map(|l| Name::from_ast_lifetime(&l)) },
                 syntax_ptr,
             ),
             ast::Expr::BreakExpr(e) => {
                 let expr = e.expr().map(|e| self.collect_expr(e));
                 self.alloc_expr(
-                    Expr::Break { expr, label: e.lifetime().map(|l| Name::new_lifetime(&l)) },
+                    Expr::Break { expr, label: e.lifetime().map(|l| Name::from_ast_lifetime(&l)) },
                     syntax_ptr,
                 )
             }
@@ -628,6 +628,7 @@ fn collect_macro_call<F, T, U>(
                     self.source_map.diagnostics.push(BodyDiagnostic::MacroError {
                         node: InFile::new(outer_file, syntax_ptr),
                         message: err.to_string(),
+                        is_compile_error: matches!(err, ExpandError::CompileError(_)),
                     });
                 }
                 None => {}
@@ -787,7 +788,7 @@ fn collect_block_opt(&mut self, expr: Option<ast::BlockExpr>) -> ExprId {
 
     fn collect_label(&mut self, ast_label: ast::Label) -> LabelId {
         let label = Label {
-            name: ast_label.lifetime().as_ref().map_or_else(Name::missing, Name::new_lifetime),
+            name: ast_label.lifetime().as_ref().map_or_else(Name::missing, Name::from_ast_lifetime),
         };
         self.alloc_label(label, AstPtr::new(&ast_label))
     }
@@ -1,10 +1,12 @@
 //! Defines database & queries for name resolution.
-use std::sync::Arc;
+use std::{iter, sync::Arc};
 
-use base_db::{salsa, CrateId, SourceDatabase, Upcast};
+use base_db::{salsa, CrateId, FileId, SourceDatabase, Upcast};
 use either::Either;
-use hir_expand::{db::AstDatabase, HirFileId};
+use hir_expand::{db::AstDatabase, name::attrs, HirFileId, MacroFile};
+use itertools::Itertools;
 use la_arena::ArenaMap;
+use limit::Limit;
 use syntax::{ast, AstPtr, SmolStr};
 
 use crate::{
@@ -20,7 +22,7 @@
     intern::Interned,
     item_tree::{AttrOwner, ItemTree},
     lang_item::{LangItemTarget, LangItems},
-    nameres::{diagnostics::DefDiagnostic, DefMap},
+    nameres::{collect_macro_files, diagnostics::DefDiagnostic, DefMap},
     visibility::{self, Visibility},
     AttrDefId, BlockId, BlockLoc, ConstId, ConstLoc, DefWithBodyId, EnumId, EnumLoc, ExternBlockId,
     ExternBlockLoc, FunctionId, FunctionLoc, GenericDefId, ImplId, ImplLoc, LocalEnumVariantId,
@@ -94,6 +96,11 @@ pub trait DefDatabase: InternDatabase + AstDatabase + Upcast<dyn AstDatabase> {
     #[salsa::invoke(DefMap::block_def_map_query)]
     fn block_def_map(&self, block: BlockId) -> Option<Arc<DefMap>>;
 
+    /// Collects the diagnostics produced while expanding the macro calls of `file_id`, both the
+    /// item-level ones and the ones in bodies, including the calls nested in their expansions.
+    #[salsa::invoke(DefMap::macro_expansion_diagnostics_query)]
+    fn macro_expansion_diagnostics(&self, file_id: HirFileId) -> Arc<Vec<DefDiagnostic>>;
+
     #[salsa::invoke(StructData::struct_data_query)]
     fn struct_data(&self, id: StructId) -> Arc<StructData>;
 
@@ -202,6 +209,11 @@ fn fields_attrs_source_map(
     fn crate_limits(&self, crate_id: CrateId) -> CrateLimits;
 
     fn crate_supports_no_std(&self, crate_id: CrateId) -> bool;
+
+    /// Returns every macro file reachable from the macro calls in `file_id`, also through the
+    /// calls in their expansions, in breadth-first order. Meant for building indices of
+    /// expansions.
+    fn all_macro_files(&self, file_id: FileId) -> Arc<Vec<MacroFile>>;
 }
 
 fn crate_def_map_wait(db: &dyn DefDatabase, krate: CrateId) -> Arc<DefMap> {
@@ -223,6 +235,33 @@ fn crate_limits(db: &dyn DefDatabase, crate_id: CrateId) -> CrateLimits {
     }
 }
 
+/// Upper bound on the number of macro files returned by `all_macro_files`.
+static ALL_MACRO_FILES_LIMIT: Limit = Limit::new(8192);
+
+fn all_macro_files(db: &dyn DefDatabase, file_id: FileId) -> Arc<Vec<MacroFile>> {
+    let _p = profile::span("all_macro_files");
+    let mut res = Vec::new();
+    let krate = match db.relevant_crates(file_id).iter().next() {
+        Some(&krate) => krate,
+        None => return Arc::new(res),
+    };
+    let is_in_file = |file: HirFileId| file.original_file(db.upcast()) == file_id;
+    collect_macro_files(db, &db.crate_def_map(krate), &is_in_file, &mut res);
+    let mut res: Vec<_> = res.into_iter().unique().collect();
+
+    // Order the files breadth-first, by the number of expansions they are nested in.
+    let depth = |file: &MacroFile| {
+        iter::successors(Some(HirFileId::from(*file)), |it| {
+            let call_id = it.macro_file()?.macro_call_id;
+            Some(db.lookup_intern_macro_call(call_id).kind.file_id())
+        })
+        .count()
+    };
+    res.sort_by_cached_key(depth);
+    res.truncate(ALL_MACRO_FILES_LIMIT.inner());
+    Arc::new(res)
+}
+
 fn crate_supports_no_std(db: &dyn DefDatabase, crate_id: CrateId) -> bool {
     let file = db.crate_graph()[crate_id].root_file_id;
     let item_tree = db.file_item_tree(file.into());
@@ -230,7 +269,7 @@ fn crate_supports_no_std(db: &dyn DefDatabase, crate_id: CrateId) -> bool {
     for attr in &**attrs {
         match attr.path().as_ident().and_then(|id| id.as_text()) {
             Some(ident) if ident == "no_std" => return true,
-            Some(ident) if ident == "cfg_attr" => {}
+            Some(ident) if ident == attrs::CFG_ATTR => {}
             _ => continue,
         }
 
@@ -250,8 +250,9 @@ fn fill_params(&mut self, lower_ctx: &LowerCtx<'_>, params: ast::GenericParamLis
             }
         }
         for lifetime_param in params.lifetime_params() {
-            let name =
-                lifetime_param.lifetime().map_or_else(Name::missing, |lt| Name::new_lifetime(&lt));
+            let name = lifetime_param
+                .lifetime()
+                .map_or_else(Name::missing, |lt| Name::from_ast_lifetime(&lt));
             let param = LifetimeParamData { name: name.clone() };
             self.lifetimes.alloc(param);
             let lifetime_ref = LifetimeRef::new_name(name);
@@ -276,7 +277,7 @@ fn fill_where_predicates(&mut self, lower_ctx: &LowerCtx<'_>, where_clause: ast:
                     .map(|lifetime_param| {
                         lifetime_param
                             .lifetime()
-                            .map_or_else(Name::missing, |lt| Name::new_lifetime(&lt))
+                            .map_or_else(Name::missing, |lt| Name::from_ast_lifetime(&lt))
                     })
                     .collect()
             });
@@ -63,6 +63,8 @@ pub struct ItemScope {
     // FIXME: Macro shadowing in one module is not properly handled. Non-item place macros will
     // be all resolved to the last one defined if shadowing happens.
     legacy_macros: FxHashMap<Name, SmallVec<[MacroId; 1]>>,
+    /// The function-like macro invocations in item position in this scope.
+    macro_invocations: FxHashMap<AstId<ast::MacroCall>, MacroCallId>,
     /// The derive macro invocations in this scope.
     attr_macros: FxHashMap<AstId<ast::Item>, MacroCallId>,
     /// The derive macro invocations in this scope, keyed by the owner item over the actual derive attributes
@@ -199,6 +201,16 @@ pub(crate) fn define_legacy_macro(&mut self, name: Name, mac: MacroId) {
         self.legacy_macros.entry(name).or_default().push(mac);
     }
 
+    pub(crate) fn add_macro_invoc(&mut self, call: AstId<ast::MacroCall>, call_id: MacroCallId) {
+        self.macro_invocations.insert(call, call_id);
+    }
+
+    pub(crate) fn macro_invocs(
+        &self,
+    ) -> impl Iterator<Item = (AstId<ast::MacroCall>, MacroCallId)> + '_ {
+        self.macro_invocations.iter().map(|(k, v)| (*k, *v))
+    }
+
     pub(crate) fn add_attr_macro_invoc(&mut self, item: AstId<ast::Item>, call: MacroCallId) {
         self.attr_macros.insert(item, call);
     }
@@ -395,6 +407,7 @@ pub(crate) fn shrink_to_fit(&mut self) {
             unnamed_consts,
             unnamed_trait_imports,
             legacy_macros,
+            macro_invocations,
             attr_macros,
             derive_macros,
         } = self;
@@ -407,6 +420,7 @@ pub(crate) fn shrink_to_fit(&mut self) {
         unnamed_consts.shrink_to_fit();
         unnamed_trait_imports.shrink_to_fit();
         legacy_macros.shrink_to_fit();
+        macro_invocations.shrink_to_fit();
         attr_macros.shrink_to_fit();
         derive_macros.shrink_to_fit();
     }
@@ -78,7 +78,7 @@ macro_rules! eprintln {
 use la_arena::Idx;
 use nameres::DefMap;
 use stdx::impl_from;
-use syntax::ast;
+use syntax::{ast, AstNode, SyntaxNode};
 
 use crate::{
     adt::VariantData,
@@ -834,6 +834,27 @@ fn as_call_id_with_errors(
     }
 }
 
+/// Expands the macro calls contained in `node`, leaving the rest of its file alone.
+///
+/// Calls that fail to resolve are skipped, and macro calls inside the expansions are not expanded
+/// further.
+pub fn expand_macros_in(
+    db: &dyn db::DefDatabase,
+    krate: CrateId,
+    node: InFile<&SyntaxNode>,
+    resolver: impl Fn(path::ModPath) -> Option<MacroDefId>,
+) -> Vec<(MacroCallId, SyntaxNode)> {
+    node.value
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter_map(|call| {
+            let call_id = node.with_value(&call).as_call_id(db, krate, &resolver)?;
+            let expanded = db.parse_or_expand(call_id.as_file())?;
+            Some((call_id, expanded))
+        })
+        .collect()
+}
+
 /// Helper wrapper for `AstId` with `ModPath`
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct AstIdWithPath<T: ast::AstNode> {
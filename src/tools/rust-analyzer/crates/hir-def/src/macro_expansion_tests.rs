@@ -21,14 +21,15 @@
 use expect_test::Expect;
 use hir_expand::{
     db::{AstDatabase, TokenExpander},
-    AstId, InFile, MacroDefId, MacroDefKind, MacroFile,
+    mod_path::ModPath,
+    pretty, AstId, InFile, MacroCallId, MacroDefId, MacroDefKind, MacroFile,
 };
 use stdx::format_to;
 use syntax::{
     ast::{self, edit::IndentLevel},
     AstNode, SyntaxElement,
-    SyntaxKind::{self, COMMENT, EOF, IDENT, LIFETIME_IDENT},
-    SyntaxNode, TextRange, T,
+    SyntaxKind::{self, COMMENT},
+    SyntaxNode, TextRange,
 };
 use tt::{Subtree, TokenId};
 
@@ -247,6 +248,31 @@ pub fn identity_when_valid(_attr: TokenStream, item: TokenStream) -> TokenStream
     expect.assert_eq(&expanded_text);
 }
 
+/// Resolves macro paths like the root module of the fixture's crate does.
+fn root_macro_resolver(db: &TestDB) -> impl Fn(ModPath) -> Option<MacroDefId> + '_ {
+    let (def_map, _) = db.root_def_map();
+    let resolver = def_map.module_id(def_map.root()).resolver(db);
+    move |path| resolver.resolve_path_as_macro(db, &path).map(|it| macro_id_to_def_id(db, it))
+}
+
+/// Resolves the fn-like macro calls of the crate root to their ids, in source order.
+fn root_macro_calls(db: &TestDB) -> Vec<(InFile<ast::MacroCall>, MacroCallId)> {
+    let (def_map, file_id) = db.root_def_map();
+    let resolver = root_macro_resolver(db);
+    db.parse(file_id)
+        .tree()
+        .syntax()
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .map(|macro_call| {
+            let macro_call = InFile::new(file_id.into(), macro_call);
+            let macro_call_id =
+                macro_call.as_ref().as_call_id(db, def_map.krate(), &resolver).unwrap();
+            (macro_call, macro_call_id)
+        })
+        .collect()
+}
+
 fn extract_id_ranges(ranges: &mut Vec<(TextRange, TokenId)>, map: &TokenMap, tree: &Subtree) {
     tree.token_trees.iter().for_each(|tree| match tree {
         tt::TokenTree::Leaf(leaf) => {
@@ -278,57 +304,16 @@ fn reindent(indent: IndentLevel, pp: String) -> String {
 }
 
 fn pretty_print_macro_expansion(expn: SyntaxNode, map: Option<&TokenMap>) -> String {
-    let mut res = String::new();
-    let mut prev_kind = EOF;
-    let mut indent_level = 0;
-    for token in iter::successors(expn.first_token(), |t| t.next_token()) {
-        let curr_kind = token.kind();
-        let space = match (prev_kind, curr_kind) {
-            _ if prev_kind.is_trivia() || curr_kind.is_trivia() => "",
-            (T!['{'], T!['}']) => "",
-            (T![=], _) | (_, T![=]) => " ",
-            (_, T!['{']) => " ",
-            (T![;] | T!['{'] | T!['}'], _) => "\n",
-            (_, T!['}']) => "\n",
-            (IDENT | LIFETIME_IDENT, IDENT | LIFETIME_IDENT) => " ",
-            _ if prev_kind.is_keyword() && curr_kind.is_keyword() => " ",
-            (IDENT, _) if curr_kind.is_keyword() => " ",
-            (_, IDENT) if prev_kind.is_keyword() => " ",
-            (T![>], IDENT) => " ",
-            (T![>], _) if curr_kind.is_keyword() => " ",
-            (T![->], _) | (_, T![->]) => " ",
-            (T![&&], _) | (_, T![&&]) => " ",
-            (T![,], _) => " ",
-            (T![:], IDENT | T!['(']) => " ",
-            (T![:], _) if curr_kind.is_keyword() => " ",
-            (T![fn], T!['(']) => "",
-            (T![']'], _) if curr_kind.is_keyword() => " ",
-            (T![']'], T![#]) => "\n",
-            (T![Self], T![::]) => "",
-            _ if prev_kind.is_keyword() => " ",
-            _ => "",
-        };
-
-        match prev_kind {
-            T!['{'] => indent_level += 1,
-            T!['}'] => indent_level -= 1,
-            _ => (),
-        }
-
-        res.push_str(space);
-        if space == "\n" {
-            let level = if curr_kind == T!['}'] { indent_level - 1 } else { indent_level };
-            res.push_str(&"    ".repeat(level));
-        }
-        prev_kind = curr_kind;
-        format_to!(res, "{}", token);
+    let tokens = iter::successors(expn.first_token(), |t| t.next_token()).map(|token| {
+        let mut text = token.to_string();
         if let Some(map) = map {
             if let Some(id) = map.token_by_range(token.text_range()) {
-                format_to!(res, "#{}", id.0);
+                format_to!(text, "#{}", id.0);
             }
         }
-    }
-    res
+        (token.kind(), text)
+    });
+    pretty::print_tokens(tokens)
 }
 
 // Identity mapping, but only works when the input is syntactically valid. This
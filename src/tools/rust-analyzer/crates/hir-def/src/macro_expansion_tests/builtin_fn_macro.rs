@@ -1,8 +1,14 @@
 //! Tests for `builtin_fn_macro.rs` from `hir_expand`.
 
+use base_db::fixture::WithFixture;
 use expect_test::expect;
+use hir_expand::{db::AstDatabase, MacroCallKind};
 
-use crate::macro_expansion_tests::check;
+use crate::{
+    macro_expansion_tests::{check, root_macro_calls},
+    nameres::diagnostics::DefDiagnosticKind,
+    test_db::TestDB,
+};
 
 #[test]
 fn test_column_expand() {
@@ -179,6 +185,33 @@ macro_rules! compile_error {
     );
 }
 
+#[test]
+fn compile_error_is_reported_at_call_site() {
+    let db = TestDB::with_files(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! compile_error {
+    ($msg:expr) => ({ /* compiler built-in */ });
+    ($msg:expr,) => ({ /* compiler built-in */ })
+}
+
+compile_error!("nope");
+"#,
+    );
+    let (def_map, _) = db.root_def_map();
+    let messages: Vec<_> = def_map
+        .diagnostics()
+        .iter()
+        .filter_map(|diag| match &diag.kind {
+            DefDiagnosticKind::MacroError { message, is_compile_error, .. } => {
+                Some((message.as_str(), *is_compile_error))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(messages, [("nope", true)]);
+}
+
 #[test]
 fn test_format_args_expand() {
     check(
@@ -308,6 +341,65 @@ macro_rules! concat {}
     );
 }
 
+#[test]
+fn asm_operands_are_not_expanded() {
+    let db = TestDB::with_files(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! asm {() => {}}
+#[rustc_builtin_macro]
+macro_rules! concat {}
+
+fn f() {
+    asm!("mov {0}, {1}", out(reg) x, in(reg) y);
+    concat!("a");
+}
+"#,
+    );
+    let calls = root_macro_calls(&db);
+    let is_asm: Vec<_> =
+        calls.iter().map(|&(_, id)| db.lookup_intern_macro_call(id).def.is_asm()).collect();
+    assert_eq!(is_asm, [true, false]);
+
+    let asm = calls[0].1;
+    assert_eq!(db.macro_expand_error(asm), None);
+    let expanded = db.parse_or_expand(asm.as_file()).unwrap().to_string();
+    assert!(expanded.contains(r#"format_args!("mov {0}, {1}")"#), "{expanded}");
+    assert!(!expanded.contains("reg"), "{expanded}");
+}
+
+#[test]
+fn test_concat_expand_nested_eager_calls() {
+    // Like rustc, only the arguments of eager macros are expanded ahead of time, after resolving
+    // the calls in them. Lazy macros get to see the unexpanded call.
+    check(
+        r##"
+#[rustc_builtin_macro]
+macro_rules! concat {}
+
+macro_rules! foo {
+    ("ab") => { struct Concatenated; };
+    ($($tt:tt)*) => { struct Raw; };
+}
+
+foo!(concat!("a", "b"));
+fn main() { concat!(concat!("a"), "b"); }
+"##,
+        expect![[r##"
+#[rustc_builtin_macro]
+macro_rules! concat {}
+
+macro_rules! foo {
+    ("ab") => { struct Concatenated; };
+    ($($tt:tt)*) => { struct Raw; };
+}
+
+struct Raw;
+fn main() { "ab"; }
+"##]],
+    );
+}
+
 #[test]
 fn test_concat_bytes_expand() {
     check(
@@ -377,3 +469,60 @@ macro_rules! concat_idents {}
 "##]],
     );
 }
+
+#[test]
+fn expansion_kinds_through_accessors() {
+    let db = TestDB::with_files(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! concat {}
+macro_rules! m {
+    () => {};
+}
+m!();
+fn f() { let _ = concat!("a"); }
+"#,
+    );
+    let ((_, items), (_, expr)) = match &*root_macro_calls(&db) {
+        [a, b] => (a.clone(), b.clone()),
+        calls => panic!("{calls:?}"),
+    };
+    let items = db.lookup_intern_macro_call(items);
+    assert!(items.def.kind.is_declarative());
+    assert!(!items.def.kind.is_builtin() && !items.def.kind.is_proc_macro());
+    match items.kind {
+        MacroCallKind::FnLike { expand_to, .. } => {
+            assert!(expand_to.is_items() && !expand_to.is_expr())
+        }
+        kind => panic!("{kind:?}"),
+    }
+
+    let expr = db.lookup_intern_macro_call(expr);
+    assert!(expr.def.kind.is_builtin() && expr.def.kind.is_eager());
+    assert!(!expr.def.kind.is_declarative());
+    match expr.kind {
+        MacroCallKind::FnLike { expand_to, .. } => assert!(expand_to.is_expr()),
+        kind => panic!("{kind:?}"),
+    }
+}
+
+#[test]
+fn expansion_handlers_name_the_expander() {
+    let db = TestDB::with_files(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! concat {}
+#[rustc_builtin_macro]
+macro_rules! stringify {}
+macro_rules! m {
+    () => {};
+}
+const _: &str = concat!("a", "b");
+const _: &str = stringify!(a);
+m!();
+"#,
+    );
+    let handlers: Vec<_> =
+        root_macro_calls(&db).into_iter().map(|(_, id)| db.expansion_handler(id)).collect();
+    assert_eq!(handlers, [Some("builtin:concat"), Some("builtin:stringify"), None]);
+}
@@ -6,9 +6,16 @@
 mod meta_syntax;
 mod regression;
 
+use base_db::{fixture::WithFixture, SourceDatabase};
 use expect_test::expect;
+use hir_expand::InFile;
+use syntax::{ast, AstNode};
 
-use crate::macro_expansion_tests::check;
+use crate::{
+    expand_macros_in,
+    macro_expansion_tests::{check, root_macro_resolver},
+    test_db::TestDB,
+};
 
 #[test]
 fn token_mapping_smoke_test() {
@@ -1675,3 +1682,24 @@ fn test() {
 "#]],
     );
 }
+
+#[test]
+fn expand_macros_in_only_touches_the_given_node() {
+    let db = TestDB::with_files(
+        r#"
+macro_rules! m {
+    ($e:expr) => { $e + 1 };
+}
+fn a() { m!(1); }
+fn b() { m!(2); m!(3); }
+"#,
+    );
+    let (def_map, file_id) = db.root_def_map();
+    let file = db.parse(file_id).tree();
+    let b = file.syntax().descendants().filter_map(ast::Fn::cast).nth(1).unwrap();
+
+    let node = InFile::new(file_id.into(), b.syntax());
+    let expansions = expand_macros_in(&db, def_map.krate(), node, root_macro_resolver(&db));
+    let texts: Vec<_> = expansions.iter().map(|(_, node)| node.to_string()).collect();
+    assert_eq!(texts, ["2+1", "3+1"]);
+}
@@ -3,9 +3,18 @@
 //! Note `//- proc_macros: identity` fixture metas in tests -- we don't use real
 //! proc-macros here, as that would be slow. Instead, we use several hard-coded
 //! in-memory macros.
+use std::sync::Arc;
+
+use base_db::{fixture::WithFixture, ProcMacro, SourceDatabase, SourceDatabaseExt};
 use expect_test::expect;
+use hir_expand::{db::AstDatabase, ExpandError, InFile};
+use syntax::{AstNode, SyntaxElement, SyntaxKind, SyntaxNodePtr};
 
-use crate::macro_expansion_tests::check;
+use crate::{
+    macro_expansion_tests::{check, root_macro_calls, IdentityWhenValidProcMacroExpander},
+    nameres::diagnostics::DefDiagnosticKind,
+    test_db::TestDB,
+};
 
 #[test]
 fn attribute_macro_attr_censoring() {
@@ -128,3 +137,207 @@ async fn foo(&self ) {
 "##]],
     );
 }
+
+#[test]
+fn proc_macro_spanned_tokens_map_up_to_input() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: spanned_fn
+proc_macros::spanned_fn!(foo);
+"#,
+    );
+    let (call, macro_call_id) = root_macro_calls(&db).pop().unwrap();
+    let file_id = macro_call_id.as_file();
+    let expansion = file_id.expansion_info(&db).unwrap();
+    let expanded = db.parse_or_expand(file_id).unwrap();
+    let token = |text: &str| {
+        expanded
+            .descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .find(|it| it.text() == text)
+            .unwrap()
+    };
+
+    let (mapped, _) = expansion.map_token_up(&db, InFile::new(file_id, &token("foo"))).unwrap();
+    assert_eq!(mapped.file_id, call.file_id);
+    assert_eq!(mapped.value.text(), "foo");
+    assert!(call.value.syntax().text_range().contains_range(mapped.value.text_range()));
+
+    assert!(expansion.map_token_up(&db, InFile::new(file_id, &token("fn"))).is_none());
+}
+
+#[test]
+fn attribute_macro_passing_item_through_maps_up() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: identity
+#[proc_macros::identity]
+fn foo() { let x = 92; }
+"#,
+    );
+    let (def_map, file_id) = db.root_def_map();
+    let root = &def_map[def_map.root()];
+    let (_, macro_call_id) = root.scope.attr_macro_invocs().next().unwrap();
+    let macro_file = macro_call_id.as_file();
+
+    let expansion = macro_file.expansion_info(&db).unwrap();
+    let expanded = db.parse_or_expand(macro_file).unwrap();
+    let token = expanded
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .find(|it| it.text() == "92")
+        .unwrap();
+
+    let (mapped, _) = expansion.map_token_up(&db, InFile::new(macro_file, &token)).unwrap();
+    assert_eq!(mapped.file_id, file_id.into());
+    let text = db.file_text(file_id);
+    assert_eq!(usize::from(mapped.value.text_range().start()), text.find("92").unwrap());
+
+    // Nothing was synthesized by the macro.
+    assert!(expansion.unmapped_regions(expanded.text_range().end()).is_empty());
+}
+
+#[test]
+fn attribute_macro_input_nodes_map_down() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: identity
+#[proc_macros::identity]
+fn foo() { let x = 1 + 2; }
+"#,
+    );
+    let (def_map, file_id) = db.root_def_map();
+    let root = &def_map[def_map.root()];
+    let (_, macro_call_id) = root.scope.attr_macro_invocs().next().unwrap();
+    let macro_file = macro_call_id.as_file();
+
+    let expansion = macro_file.expansion_info(&db).unwrap();
+    let expanded = db.parse_or_expand(macro_file).unwrap();
+    let source = db.parse(file_id).syntax_node();
+    let map_down = |kind: SyntaxKind| {
+        let node = source.descendants().find(|it| it.kind() == kind).unwrap();
+        let mapped = expansion.map_node_down(SyntaxNodePtr::new(&node), &expanded)?;
+        Some((mapped.kind(), mapped.to_string()))
+    };
+
+    assert_eq!(map_down(SyntaxKind::BIN_EXPR), Some((SyntaxKind::BIN_EXPR, "1+2".to_string())));
+    assert_eq!(
+        map_down(SyntaxKind::LET_STMT),
+        Some((SyntaxKind::LET_STMT, "letx=1+2;".to_string()))
+    );
+    // The attribute invoking the macro is not part of its input.
+    assert_eq!(map_down(SyntaxKind::ATTR), None);
+}
+
+#[test]
+fn derive_expanding_to_expression_is_an_error() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: derive_expr
+//- minicore: derive
+#[derive(proc_macros::DeriveExpr)]
+struct S;
+"#,
+    );
+    let (def_map, _) = db.root_def_map();
+    let messages: Vec<_> = def_map
+        .diagnostics()
+        .iter()
+        .filter_map(|diag| match &diag.kind {
+            DefDiagnosticKind::MacroError { message, .. } => Some(message.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(messages, [ExpandError::DeriveOutputNotItems.to_string()]);
+}
+
+#[test]
+fn derive_helper_attributes_are_not_diagnosed() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: derive_with_helper
+//- minicore: derive
+#[derive(proc_macros::DeriveWithHelper)]
+#[helper(rename = "T")]
+struct S {
+    #[helper]
+    field: u32,
+}
+"#,
+    );
+    let (def_map, _) = db.root_def_map();
+    assert!(def_map.diagnostics().is_empty(), "{:?}", def_map.diagnostics());
+
+    let root = &def_map[def_map.root()];
+    let (adt, _) = root.scope.derive_macro_invocs().next().unwrap();
+    let helpers = def_map.derive_helpers_in_scope(adt).unwrap();
+    assert_eq!(helpers.iter().map(|(name, ..)| name.to_string()).collect::<Vec<_>>(), ["helper"]);
+}
+
+#[test]
+fn unresolved_proc_macro_skips_argument_lowering() {
+    // `nonexistent` is declared by the proc-macro crate, but no expander was loaded for it.
+    let db = TestDB::with_files_extra_proc_macros(
+        r#"
+//- proc_macros: identity_when_valid
+proc_macros::nonexistent!(foo);
+"#,
+        vec![(
+            r#"
+#[proc_macro_attribute]
+pub fn identity_when_valid(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+#[proc_macro]
+pub fn nonexistent(input: TokenStream) -> TokenStream {
+    input
+}
+"#
+            .into(),
+            ProcMacro {
+                name: "identity_when_valid".into(),
+                kind: base_db::ProcMacroKind::Attr,
+                expander: Arc::new(IdentityWhenValidProcMacroExpander),
+            },
+        )],
+    );
+    let events = db.log_executed(|| {
+        let (_, macro_call_id) = root_macro_calls(&db).pop().unwrap();
+        let res = db.macro_expand(macro_call_id);
+        assert!(res.value.is_none());
+        assert!(matches!(res.err, Some(ExpandError::UnresolvedProcMacro(_))));
+    });
+    assert!(!events.iter().any(|it| it.contains("macro_arg_text")), "{events:#?}");
+}
+
+#[test]
+fn attribute_macro_on_module() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: identity
+#[proc_macros::identity]
+mod m {
+    pub struct S;
+}
+use m::S;
+"#,
+    );
+    let (def_map, file_id) = db.root_def_map();
+
+    // The module behaves like a real one for resolution...
+    let root = &def_map[def_map.root()];
+    assert!(root
+        .scope
+        .entries()
+        .any(|(name, def)| name.to_string() == "S" && def.take_types().is_some()));
+
+    // ...while being declared in the expansion, which maps back to the original module.
+    let (_, m) = def_map.modules().find(|(id, _)| *id != def_map.root()).unwrap();
+    let decl = m.declaration_source(&db).unwrap();
+    assert!(decl.file_id.is_macro());
+    assert_eq!(decl.file_id.original_file(&db), file_id);
+
+    let range = decl.as_ref().map(|it| it.syntax()).original_file_range(&db);
+    assert_eq!(range.file_id, file_id);
+    assert_eq!(&db.file_text(file_id)[range.range], "mod m {\n    pub struct S;\n}");
+}
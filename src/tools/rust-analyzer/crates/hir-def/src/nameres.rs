@@ -60,23 +60,30 @@
 use std::{cmp::Ord, ops::Deref, sync::Arc};
 
 use base_db::{CrateId, Edition, FileId};
-use hir_expand::{name::Name, InFile, MacroCallId, MacroDefId};
+use hir_expand::{
+    name::Name, ExpandTo, HirFileId, InFile, MacroCallId, MacroCallKind, MacroDefId, MacroFile,
+};
 use itertools::Itertools;
 use la_arena::Arena;
 use profile::Count;
 use rustc_hash::{FxHashMap, FxHashSet};
 use stdx::format_to;
-use syntax::{ast, SmolStr};
+use syntax::{ast, AstPtr, SmolStr};
 
 use crate::{
+    body::BodyDiagnostic,
     db::DefDatabase,
     item_scope::{BuiltinShadowMode, ItemScope},
     item_tree::{ItemTreeId, Mod, TreeId},
-    nameres::{diagnostics::DefDiagnostic, path_resolution::ResolveMode},
+    nameres::{
+        diagnostics::{DefDiagnostic, DefDiagnosticKind},
+        path_resolution::ResolveMode,
+    },
     path::ModPath,
     per_ns::PerNs,
     visibility::Visibility,
-    AstId, BlockId, BlockLoc, FunctionId, LocalModuleId, MacroId, ModuleId, ProcMacroId,
+    AdtId, AssocItemId, AstId, BlockId, BlockLoc, DefWithBodyId, EnumVariantId, FunctionId,
+    HasModule, LocalModuleId, Lookup, MacroId, ModuleDefId, ModuleId, ProcMacroId,
 };
 
 /// Contains the results of (early) name resolution.
@@ -270,6 +277,38 @@ pub(crate) fn block_def_map_query(
         Some(Arc::new(def_map))
     }
 
+    pub(crate) fn macro_expansion_diagnostics_query(
+        db: &dyn DefDatabase,
+        file_id: HirFileId,
+    ) -> Arc<Vec<DefDiagnostic>> {
+        let _p = profile::span("macro_expansion_diagnostics_query");
+        let original_file = match file_id.try_original_file(db.upcast()) {
+            Some(it) => it,
+            // Synthetic files are not part of any crate.
+            None => return Arc::new(Vec::new()),
+        };
+
+        // Whether `file` is `file_id` or an expansion of a macro call nested in it.
+        let is_in_file = |mut curr: HirFileId| loop {
+            if curr == file_id {
+                break true;
+            }
+            match curr.macro_file() {
+                Some(macro_file) => {
+                    curr = db.lookup_intern_macro_call(macro_file.macro_call_id).kind.file_id()
+                }
+                None => break false,
+            }
+        };
+
+        let mut res = Vec::new();
+        for &krate in db.relevant_crates(original_file).iter() {
+            let def_map = db.crate_def_map(krate);
+            collect_macro_expansion_diagnostics(db, &def_map, &is_in_file, &mut res);
+        }
+        Arc::new(res)
+    }
+
     fn empty(krate: CrateId, edition: Edition, module_data: ModuleData) -> DefMap {
         let mut modules: Arena<ModuleData> = Arena::default();
         let root = modules.alloc(module_data);
@@ -360,7 +399,11 @@ pub fn module_id(&self, local_id: LocalModuleId) -> ModuleId {
 
     pub(crate) fn crate_root(&self, db: &dyn DefDatabase) -> ModuleId {
         self.with_ancestor_maps(db, self.root, &mut |def_map, _module| {
-            if def_map.block.is_none() { Some(def_map.module_id(def_map.root)) } else { None }
+            if def_map.block.is_none() {
+                Some(def_map.module_id(def_map.root))
+            } else {
+                None
+            }
         })
         .expect("DefMap chain without root")
     }
@@ -550,6 +593,164 @@ pub fn declaration_source(&self, db: &dyn DefDatabase) -> Option<InFile<ast::Mod
     }
 }
 
+/// Collects the macro diagnostics of `def_map` and of the bodies of its items that `is_in_file`
+/// accepts. Lowering a body expands the macro calls in it, and its block expressions with inner
+/// items get def maps of their own, which are walked in turn.
+fn collect_macro_expansion_diagnostics(
+    db: &dyn DefDatabase,
+    def_map: &DefMap,
+    is_in_file: &dyn Fn(HirFileId) -> bool,
+    acc: &mut Vec<DefDiagnostic>,
+) {
+    acc.extend(
+        def_map
+            .diagnostics()
+            .iter()
+            .filter(|diag| match &diag.kind {
+                DefDiagnosticKind::UnresolvedProcMacro { ast, .. }
+                | DefDiagnosticKind::UnresolvedMacroCall { ast, .. }
+                | DefDiagnosticKind::MacroError { ast, .. } => is_in_file(ast.file_id()),
+                _ => false,
+            })
+            .cloned(),
+    );
+
+    for (def, def_file) in bodies(db, def_map) {
+        if !is_in_file(def_file) {
+            continue;
+        }
+        let (body, source_map) = db.body_with_source_map(def);
+        let in_module = def.module(db).local_id;
+        let call_kind = |node: &InFile<AstPtr<ast::MacroCall>>| match is_in_file(node.file_id) {
+            true => fn_like_call_kind(db, node),
+            false => None,
+        };
+        acc.extend(source_map.diagnostics().iter().filter_map(|diag| {
+            let kind = match diag {
+                BodyDiagnostic::InactiveCode { .. } => return None,
+                BodyDiagnostic::MacroError { node, message, is_compile_error } => {
+                    DefDiagnosticKind::MacroError {
+                        ast: call_kind(node)?,
+                        message: message.clone(),
+                        is_compile_error: *is_compile_error,
+                    }
+                }
+                BodyDiagnostic::UnresolvedProcMacro { node, krate } => {
+                    DefDiagnosticKind::UnresolvedProcMacro { ast: call_kind(node)?, krate: *krate }
+                }
+                BodyDiagnostic::UnresolvedMacroCall { node, path } => {
+                    DefDiagnosticKind::UnresolvedMacroCall {
+                        ast: call_kind(node)?,
+                        path: path.clone(),
+                    }
+                }
+            };
+            Some(DefDiagnostic { in_module, kind })
+        }));
+        for (_, block_def_map) in body.blocks(db) {
+            collect_macro_expansion_diagnostics(db, &block_def_map, is_in_file, acc);
+        }
+    }
+}
+
+/// Collects the macro files of the calls recorded in `def_map` and in the bodies of its items that
+/// `is_in_file` accepts, walking the def maps of block expressions in turn.
+///
+/// Every call is resolved in its own scope when the def map is built or the body is lowered, and
+/// expansions are only recorded up to the recursion limit.
+pub(crate) fn collect_macro_files(
+    db: &dyn DefDatabase,
+    def_map: &DefMap,
+    is_in_file: &dyn Fn(HirFileId) -> bool,
+    acc: &mut Vec<MacroFile>,
+) {
+    for (_, module) in def_map.modules() {
+        let scope = &module.scope;
+        let fn_likes = scope.macro_invocs().map(|(_, call)| call);
+        let attrs = scope.attr_macro_invocs().map(|(_, call)| call);
+        let derives = scope.derive_macro_invocs().flat_map(|(_, derives)| {
+            derives.flat_map(|(_, _, calls)| calls.iter().flatten().copied())
+        });
+        acc.extend(
+            fn_likes
+                .chain(attrs)
+                .chain(derives)
+                .map(|call| MacroFile { macro_call_id: call })
+                .filter(|&file| is_in_file(file.into())),
+        );
+    }
+
+    for (def, def_file) in bodies(db, def_map) {
+        if !is_in_file(def_file) {
+            continue;
+        }
+        let (body, source_map) = db.body_with_source_map(def);
+        acc.extend(source_map.macro_calls().filter_map(|(_, file)| file.macro_file()));
+        for (_, block_def_map) in body.blocks(db) {
+            collect_macro_files(db, &block_def_map, is_in_file, acc);
+        }
+    }
+}
+
+/// Returns the items of `def_map` that have a body, also the ones in traits and impls and the
+/// variants of enums, along with the files they are defined in.
+fn bodies(db: &dyn DefDatabase, def_map: &DefMap) -> Vec<(DefWithBodyId, HirFileId)> {
+    let assoc_body = |it: AssocItemId| match it {
+        AssocItemId::FunctionId(it) => Some(DefWithBodyId::from(it)),
+        AssocItemId::ConstId(it) => Some(it.into()),
+        AssocItemId::TypeAliasId(_) => None,
+    };
+    let mut bodies = Vec::new();
+    for (_, module) in def_map.modules() {
+        let scope = &module.scope;
+        for decl in scope.declarations() {
+            match decl {
+                ModuleDefId::FunctionId(it) => bodies.push(it.into()),
+                ModuleDefId::ConstId(it) => bodies.push(it.into()),
+                ModuleDefId::StaticId(it) => bodies.push(it.into()),
+                ModuleDefId::TraitId(it) => {
+                    let data = db.trait_data(it);
+                    bodies.extend(data.items.iter().filter_map(|&(_, it)| assoc_body(it)));
+                }
+                ModuleDefId::AdtId(AdtId::EnumId(parent)) => {
+                    bodies.extend(db.enum_data(parent).variants.iter().map(|(local_id, _)| {
+                        DefWithBodyId::VariantId(EnumVariantId { parent, local_id })
+                    }))
+                }
+                _ => {}
+            }
+        }
+        for impl_ in scope.impls() {
+            bodies.extend(db.impl_data(impl_).items.iter().copied().filter_map(assoc_body));
+        }
+        bodies.extend(scope.unnamed_consts().map(DefWithBodyId::from));
+    }
+
+    bodies
+        .into_iter()
+        .map(|def| {
+            let def_file = match def {
+                DefWithBodyId::FunctionId(it) => it.lookup(db).id.file_id(),
+                DefWithBodyId::StaticId(it) => it.lookup(db).id.file_id(),
+                DefWithBodyId::ConstId(it) => it.lookup(db).id.file_id(),
+                DefWithBodyId::VariantId(it) => it.parent.lookup(db).id.file_id(),
+            };
+            (def, def_file)
+        })
+        .collect()
+}
+
+fn fn_like_call_kind(
+    db: &dyn DefDatabase,
+    node: &InFile<AstPtr<ast::MacroCall>>,
+) -> Option<MacroCallKind> {
+    let call = node.value.to_node(&db.parse_or_expand(node.file_id)?);
+    Some(MacroCallKind::FnLike {
+        ast_id: node.with_value(db.ast_id_map(node.file_id).ast_id(&call)),
+        expand_to: ExpandTo::from_call_site(&call),
+    })
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ModuleSource {
     SourceFile(ast::SourceFile),
@@ -1098,6 +1098,9 @@ fn resolve_macros(&mut self) -> ReachedFixedPoint {
                         &mut |_err| (),
                     );
                     if let Ok(Ok(call_id)) = call_id {
+                        self.def_map.modules[directive.module_id]
+                            .scope
+                            .add_macro_invoc(ast_id.ast_id, call_id);
                         push_resolved(directive, call_id);
                         res = ReachedFixedPoint::No;
                         return false;
@@ -1326,9 +1329,16 @@ fn collect_macro_expansion(
         depth: usize,
         container: ItemContainerId,
     ) {
+        let loc: MacroCallLoc = self.db.lookup_intern_macro_call(macro_call_id);
         if EXPANSION_DEPTH_LIMIT.check(depth).is_err() {
             cov_mark::hit!(macro_expansion_overflow);
             tracing::warn!("macro expansion is too deep");
+            // Report it: stopping the recursion here silently would leave the expansion truncated.
+            self.def_map.diagnostics.push(DefDiagnostic::macro_error(
+                module_id,
+                loc.kind,
+                &hir_expand::ExpandError::RecursionLimitExceeded,
+            ));
             return;
         }
         let file_id = macro_call_id.as_file();
@@ -1336,7 +1346,6 @@ fn collect_macro_expansion(
         // First, fetch the raw expansion result for purposes of error reporting. This goes through
         // `macro_expand_error` to avoid depending on the full expansion result (to improve
         // incrementality).
-        let loc: MacroCallLoc = self.db.lookup_intern_macro_call(macro_call_id);
         let err = self.db.macro_expand_error(macro_call_id);
         if let Some(err) = err {
             let diag = match err {
@@ -1345,7 +1354,7 @@ fn collect_macro_expansion(
                     // Missing proc macros are non-fatal, so they are handled specially.
                     DefDiagnostic::unresolved_proc_macro(module_id, loc.kind.clone(), loc.def.krate)
                 }
-                _ => DefDiagnostic::macro_error(module_id, loc.kind, err.to_string()),
+                _ => DefDiagnostic::macro_error(module_id, loc.kind, &err),
             };
 
             self.def_map.diagnostics.push(diag);
@@ -2095,6 +2104,9 @@ fn collect_macro_call(&mut self, mac: &MacroCall, container: ItemContainerId) {
             },
         ) {
             Ok(Ok(macro_call_id)) => {
+                self.def_collector.def_map.modules[self.module_id]
+                    .scope
+                    .add_macro_invoc(ast_id.ast_id, macro_call_id);
                 // Legacy macros need to be expanded immediately, so that any macros they produce
                 // are in scope.
                 self.def_collector.collect_macro_expansion(
@@ -2108,7 +2120,7 @@ fn collect_macro_call(&mut self, mac: &MacroCall, container: ItemContainerId) {
                     self.def_collector.def_map.diagnostics.push(DefDiagnostic::macro_error(
                         self.module_id,
                         MacroCallKind::FnLike { ast_id: ast_id.ast_id, expand_to: mac.expand_to },
-                        err.to_string(),
+                        &err,
                     ));
                 }
 
@@ -2120,7 +2132,7 @@ fn collect_macro_call(&mut self, mac: &MacroCall, container: ItemContainerId) {
                 self.def_collector.def_map.diagnostics.push(DefDiagnostic::macro_error(
                     self.module_id,
                     MacroCallKind::FnLike { ast_id: ast_id.ast_id, expand_to: mac.expand_to },
-                    error.unwrap().to_string(),
+                    &error.unwrap(),
                 ));
                 return;
             }
@@ -2,7 +2,7 @@
 
 use base_db::CrateId;
 use cfg::{CfgExpr, CfgOptions};
-use hir_expand::MacroCallKind;
+use hir_expand::{ExpandError, MacroCallKind};
 use la_arena::Idx;
 use syntax::ast::{self, AnyHasAttrs};
 
@@ -14,7 +14,7 @@
     AstId,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DefDiagnosticKind {
     UnresolvedModule { ast: AstId<ast::Module>, candidates: Box<[String]> },
 
@@ -28,7 +28,7 @@ pub enum DefDiagnosticKind {
 
     UnresolvedMacroCall { ast: MacroCallKind, path: ModPath },
 
-    MacroError { ast: MacroCallKind, message: String },
+    MacroError { ast: MacroCallKind, message: String, is_compile_error: bool },
 
     UnimplementedBuiltinMacro { ast: AstId<ast::Macro> },
 
@@ -37,7 +37,7 @@ pub enum DefDiagnosticKind {
     MalformedDerive { ast: AstId<ast::Adt>, id: u32 },
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DefDiagnostic {
     pub in_module: LocalModuleId,
     pub kind: DefDiagnosticKind,
@@ -93,9 +93,13 @@ pub(super) fn unresolved_proc_macro(
     pub(super) fn macro_error(
         container: LocalModuleId,
         ast: MacroCallKind,
-        message: String,
+        err: &ExpandError,
     ) -> Self {
-        Self { in_module: container, kind: DefDiagnosticKind::MacroError { ast, message } }
+        let is_compile_error = matches!(err, ExpandError::CompileError(_));
+        Self {
+            in_module: container,
+            kind: DefDiagnosticKind::MacroError { ast, message: err.to_string(), is_compile_error },
+        }
     }
 
     pub(super) fn unresolved_macro_call(
@@ -166,6 +166,57 @@ fn quux() { 92 }
     }
 }
 
+#[test]
+fn editing_macro_definition_file_elsewhere_should_not_invalidate_expansions() {
+    let (mut db, pos) = TestDB::with_position(
+        r#"
+//- /lib.rs
+macro_rules! m {
+    ($ident:ident) => {
+        fn $ident() { };
+    }
+}
+mod foo;
+fn quux() { 1$0 }
+
+//- /foo.rs
+m!(X);
+m!(Y);
+"#,
+    );
+    let krate = db.test_crate();
+    {
+        let events = db.log_executed(|| {
+            db.crate_def_map(krate);
+        });
+        let n_expanded_macros = events.iter().filter(|it| it.contains("macro_expand(")).count();
+        assert_eq!(n_expanded_macros, 2);
+    }
+
+    let new_text = r#"
+macro_rules! m {
+    ($ident:ident) => {
+        fn $ident() { };
+    }
+}
+mod foo;
+fn quux() { 92 }
+"#;
+    db.set_file_text(pos.file_id, Arc::new(new_text.to_string()));
+
+    {
+        let events = db.log_executed(|| {
+            db.crate_def_map(krate);
+        });
+        assert!(events.iter().any(|it| it.contains("macro_def(")), "{events:#?}");
+        let n_expanded_macros = events.iter().filter(|it| it.contains("macro_expand(")).count();
+        assert_eq!(n_expanded_macros, 0, "{events:#?}");
+        let n_reparsed_macros =
+            events.iter().filter(|it| it.contains("parse_macro_expansion")).count();
+        assert_eq!(n_reparsed_macros, 0);
+    }
+}
+
 #[test]
 fn item_tree_prevents_reparsing() {
     // The `ItemTree` is used by both name resolution and the various queries in `adt.rs` and
@@ -235,3 +286,36 @@ impl Tr for () {}
         assert_eq!(n_reparsed_files, 0);
     }
 }
+
+#[test]
+fn fan_out_expansions_run_once_per_call() {
+    let db = TestDB::with_files(
+        r#"
+macro_rules! helper {
+    ($i:ident) => { struct $i; };
+}
+macro_rules! fan {
+    (a) => { helper!(A); helper!(B); };
+    (b) => { helper!(C); };
+}
+fan!(a);
+fan!(b);
+"#,
+    );
+    let krate = db.test_crate();
+    let file_id = db.crate_graph()[krate].root_file_id;
+    let count_expansions =
+        |events: Vec<String>| events.iter().filter(|it| it.contains("macro_expand(")).count();
+
+    // Every call is a distinct `MacroCallId`, which salsa expands exactly once, no matter how
+    // many times the expansion is asked for while collecting the crate.
+    let events = db.log_executed(|| {
+        db.crate_def_map(krate);
+    });
+    assert_eq!(count_expansions(events), 5);
+
+    let events = db.log_executed(|| {
+        assert_eq!(db.all_macro_files(file_id).len(), 5);
+    });
+    assert_eq!(count_expansions(events), 0);
+}
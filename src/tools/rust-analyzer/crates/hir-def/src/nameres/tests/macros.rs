@@ -1,6 +1,10 @@
 use super::*;
+use base_db::SourceDatabaseExt;
+use hir_expand::db::AstDatabase;
 use itertools::Itertools;
 
+use crate::nameres::diagnostics::DefDiagnosticKind;
+
 #[test]
 fn macro_rules_are_globally_visible() {
     check(
@@ -886,6 +890,118 @@ macro_rules! b {
     );
 }
 
+#[test]
+fn macro_expansion_diagnostics_of_file() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+macro_rules! good {
+    () => { struct S; };
+}
+
+macro_rules! recursive {
+    () => { recursive!(); };
+}
+
+good!();
+recursive!();
+"#,
+    );
+    let diagnostics = db.macro_expansion_diagnostics(file_id.into());
+    assert_eq!(diagnostics.len(), 1);
+    match &diagnostics[0].kind {
+        DefDiagnosticKind::MacroError { ast, message, is_compile_error } => {
+            assert_eq!(message, "macro expansion is too deep");
+            assert!(!*is_compile_error);
+            // The call hitting the limit is nested in the expansions, but maps back to the call
+            // written in the file.
+            assert!(ast.file_id().macro_file().is_some());
+            let range = ast.clone().original_call_range(&db);
+            assert_eq!(range.file_id, file_id);
+            let text = db.file_text(file_id);
+            assert_eq!(usize::from(range.range.start()), text.rfind("recursive!();").unwrap());
+            assert_eq!(&text[range.range], "recursive!();");
+        }
+        kind => panic!("unexpected diagnostic: {kind:?}"),
+    }
+}
+
+#[test]
+fn macro_expansion_diagnostics_of_bodies() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+macro_rules! good {
+    () => { 0 };
+}
+
+macro_rules! recursive {
+    () => { recursive!() };
+}
+
+macro_rules! item {
+    () => { struct S; };
+}
+
+fn f() {
+    let _ = good!();
+    let _ = recursive!();
+    {
+        item!();
+        unresolved!();
+    }
+}
+"#,
+    );
+    let diagnostics = db.macro_expansion_diagnostics(file_id.into());
+    let text = db.file_text(file_id);
+    let actual = diagnostics
+        .iter()
+        .map(|diag| match &diag.kind {
+            DefDiagnosticKind::MacroError { ast, message, .. } => {
+                let range = ast.clone().original_call_range(&db);
+                format!("{}: {message}", &text[range.range])
+            }
+            DefDiagnosticKind::UnresolvedMacroCall { ast, path } => {
+                let range = ast.clone().original_call_range(&db);
+                format!("{}: unresolved {path}", &text[range.range])
+            }
+            kind => panic!("unexpected diagnostic: {kind:?}"),
+        })
+        .join("\n");
+    assert_eq!(
+        actual,
+        "recursive!(): reached recursion limit during macro expansion\n\
+         unresolved!(): unresolved unresolved"
+    );
+}
+
+#[test]
+fn nonexistent_macro_is_never_expanded() {
+    let db = TestDB::with_files(
+        r#"
+macro_rules! exists {
+    () => { struct S; };
+}
+nonexistent!(struct T;);
+"#,
+    );
+    let krate = db.test_crate();
+    let events = db.log_executed(|| {
+        let def_map = db.crate_def_map(krate);
+        let paths: Vec<_> = def_map
+            .diagnostics()
+            .iter()
+            .filter_map(|diag| match &diag.kind {
+                DefDiagnosticKind::UnresolvedMacroCall { path, .. } => Some(path.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths, ["nonexistent"]);
+    });
+    // The call fails to resolve before a `MacroCallId` is made for it, so its argument is never
+    // lowered into a token tree.
+    assert!(!events.iter().any(|it| it.contains("macro_arg")), "{events:#?}");
+}
+
 #[test]
 fn macros_defining_macros() {
     check(
@@ -907,6 +1023,31 @@ macro_rules! indirect_macro { () => { struct S {} } }
     );
 }
 
+#[test]
+fn macros_defining_macros_in_their_body() {
+    check(
+        r#"
+macro_rules! a {
+    () => {
+        macro_rules! b { () => { struct S; } }
+        #[macro_export]
+        macro_rules! c { () => { struct T; } }
+    };
+}
+
+a!();
+b!();
+crate::c!();
+    "#,
+        expect![[r#"
+            crate
+            S: t v
+            T: t v
+            c: m
+        "#]],
+    );
+}
+
 #[test]
 fn resolves_proc_macros() {
     check(
@@ -1030,6 +1171,28 @@ fn resolve_macro_def() {
     );
 }
 
+#[test]
+fn macro_2_0_rules_are_path_scoped() {
+    check(
+        r#"
+mod a {
+    pub macro m {
+        ($name:ident) => { pub struct $name; },
+    }
+}
+a::m!(S);
+"#,
+        expect![[r#"
+            crate
+            S: t v
+            a: t
+
+            crate::a
+            m: m
+        "#]],
+    );
+}
+
 #[test]
 fn macro_in_prelude() {
     check(
@@ -1207,3 +1370,57 @@ fn proc_attr(a: TokenStream, b: TokenStream) -> TokenStream { a }
     "#]]
     .assert_eq(&actual);
 }
+
+#[test]
+fn all_macro_files_includes_nested_expansions() {
+    let db = TestDB::with_files(
+        r#"
+//- proc_macros: identity
+macro_rules! inner {
+    ($i:ident) => { struct $i; };
+}
+macro_rules! outer {
+    () => { inner!(A); fn f() { inner!(B); } };
+}
+outer!();
+#[proc_macros::identity]
+struct G;
+"#,
+    );
+    let (_, file_id) = db.root_def_map();
+    let macro_files = db.all_macro_files(file_id);
+    let mut texts: Vec<_> =
+        macro_files.iter().map(|&it| db.parse_or_expand(it.into()).unwrap().to_string()).collect();
+    texts.sort();
+    assert_eq!(texts, ["inner!(A);fnf(){inner!(B);}", "structA;", "structB;", "structG;"]);
+}
+
+#[test]
+fn all_macro_files_resolves_calls_in_their_own_scope() {
+    let db = TestDB::with_files(
+        r#"
+macro_rules! value {
+    () => { 92 };
+}
+mod m {
+    macro_rules! local {
+        () => { struct L; };
+    }
+    local!();
+}
+fn f() {
+    macro_rules! in_block {
+        () => { struct K; };
+    }
+    in_block!();
+    let _ = value!();
+}
+"#,
+    );
+    let (_, file_id) = db.root_def_map();
+    let macro_files = db.all_macro_files(file_id);
+    let mut texts: Vec<_> =
+        macro_files.iter().map(|&it| db.parse_or_expand(it.into()).unwrap().to_string()).collect();
+    texts.sort();
+    assert_eq!(texts, ["92", "structK;", "structL;"]);
+}
@@ -9,7 +9,10 @@
     salsa, AnchoredPath, CrateId, FileId, FileLoader, FileLoaderDelegate, FilePosition,
     SourceDatabase, Upcast,
 };
-use hir_expand::{db::AstDatabase, InFile};
+use hir_expand::{
+    db::{AstDatabase, ExpansionProvenanceLog, HasExpansionProvenance},
+    InFile,
+};
 use stdx::hash::NoHashHashSet;
 use syntax::{algo, ast, AstNode};
 
@@ -30,11 +33,16 @@
 pub(crate) struct TestDB {
     storage: salsa::Storage<TestDB>,
     events: Mutex<Option<Vec<salsa::Event>>>,
+    expansion_provenance: Arc<ExpansionProvenanceLog>,
 }
 
 impl Default for TestDB {
     fn default() -> Self {
-        let mut this = Self { storage: Default::default(), events: Default::default() };
+        let mut this = Self {
+            storage: Default::default(),
+            events: Default::default(),
+            expansion_provenance: Default::default(),
+        };
         this.set_enable_proc_attr_macros(true);
         this
     }
@@ -52,6 +60,12 @@ fn upcast(&self) -> &(dyn DefDatabase + 'static) {
     }
 }
 
+impl HasExpansionProvenance for TestDB {
+    fn expansion_provenance_log(&self) -> &ExpansionProvenanceLog {
+        &self.expansion_provenance
+    }
+}
+
 impl salsa::Database for TestDB {
     fn salsa_event(&self, event: salsa::Event) {
         let mut events = self.events.lock().unwrap();
@@ -82,6 +96,14 @@ fn relevant_crates(&self, file_id: FileId) -> Arc<NoHashHashSet<CrateId>> {
 }
 
 impl TestDB {
+    /// Returns the def map of the fixture's first crate, the one its files go to unless they are
+    /// assigned to crates explicitly, along with the file of its crate root.
+    pub(crate) fn root_def_map(&self) -> (Arc<DefMap>, FileId) {
+        let crate_graph = self.crate_graph();
+        let krate = crate_graph.iter().next().unwrap();
+        (self.crate_def_map(krate), crate_graph[krate].root_file_id)
+    }
+
     pub(crate) fn module_for_file(&self, file_id: FileId) -> ModuleId {
         for &krate in self.relevant_crates(file_id).iter() {
             let crate_def_map = self.crate_def_map(krate);
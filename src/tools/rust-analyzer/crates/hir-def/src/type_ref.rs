@@ -137,7 +137,7 @@ pub(crate) fn new_name(name: Name) -> Self {
     }
 
     pub(crate) fn new(lifetime: &ast::Lifetime) -> Self {
-        LifetimeRef { name: Name::new_lifetime(lifetime) }
+        LifetimeRef { name: Name::from_ast_lifetime(lifetime) }
     }
 
     pub fn missing() -> LifetimeRef {
@@ -349,7 +349,7 @@ pub(crate) fn from_ast(ctx: &LowerCtx<'_>, node: ast::TypeBound) -> Self {
                 let lt_refs = match for_type.generic_param_list() {
                     Some(gpl) => gpl
                         .lifetime_params()
-                        .flat_map(|lp| lp.lifetime().map(|lt| Name::new_lifetime(&lt)))
+                        .flat_map(|lp| lp.lifetime().map(|lt| Name::from_ast_lifetime(&lt)))
                         .collect(),
                     None => Box::default(),
                 };
@@ -48,6 +48,21 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<N: AstNode> serde::Serialize for FileAstId<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.raw.into_raw().into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, N: AstNode> serde::Deserialize<'de> for FileAstId<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(FileAstId { raw: Idx::from_raw(raw.into()), _ty: PhantomData })
+    }
+}
+
 impl<N: AstNode> FileAstId<N> {
     // Can't make this a From implementation because of coherence
     pub fn upcast<M: AstNode>(self) -> FileAstId<M>
@@ -179,3 +194,29 @@ fn bdfs(node: &SyntaxNode, mut f: impl FnMut(SyntaxNode) -> bool) {
         std::mem::swap(&mut curr_layer, &mut next_layer);
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use base_db::FileId;
+    use syntax::{
+        ast::{self, HasModuleItem},
+        AstNode, SourceFile,
+    };
+
+    use super::AstIdMap;
+    use crate::{AstId, HirFileId};
+
+    #[test]
+    fn ast_id_serde_round_trip() {
+        let file = SourceFile::parse("fn f() {} struct S;").tree();
+        let map = AstIdMap::from_source(file.syntax());
+        let item = file.items().nth(1).unwrap();
+        let ast_id: AstId<ast::Item> = AstId::new(FileId(0).into(), map.ast_id(&item));
+
+        let json = serde_json::to_string(&ast_id).unwrap();
+        let rehydrated: AstId<ast::Item> = serde_json::from_str(&json).unwrap();
+        assert_eq!(rehydrated, ast_id);
+        assert_eq!(rehydrated.file_id, HirFileId::from(FileId(0)));
+        assert_eq!(map.get(rehydrated.value).to_node(file.syntax()), item);
+    }
+}
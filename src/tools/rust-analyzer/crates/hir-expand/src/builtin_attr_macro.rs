@@ -22,6 +22,14 @@ pub fn expand(
                 expander(db, id, tt)
             }
 
+            /// The label [`expansion_handler`](crate::db::AstDatabase::expansion_handler) reports
+            /// for this attribute, like `builtin:test`.
+            pub fn label(&self) -> &'static str {
+                match *self {
+                    $( BuiltinAttrExpander::$variant => concat!("builtin:", stringify!($name)), )*
+                }
+            }
+
             fn find_by_name(name: &name::Name) -> Option<Self> {
                 match name {
                     $( id if id == &name::name![$name] => Some(BuiltinAttrExpander::$variant), )*
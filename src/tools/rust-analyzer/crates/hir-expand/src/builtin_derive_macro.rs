@@ -31,6 +31,14 @@ pub fn expand(
                 expander(db, id, tt)
             }
 
+            /// The label [`expansion_handler`](crate::db::AstDatabase::expansion_handler) reports
+            /// for this derive, like `builtin:Clone`.
+            pub fn label(&self) -> &'static str {
+                match *self {
+                    $( BuiltinDeriveExpander::$trait => concat!("builtin:", stringify!($trait)), )*
+                }
+            }
+
             fn find_by_name(name: &name::Name) -> Option<Self> {
                 match name {
                     $( id if id == &name::name![$trait] => Some(BuiltinDeriveExpander::$trait), )*
@@ -35,6 +35,14 @@ pub fn expand(
                 };
                 expander(db, id, tt)
             }
+
+            /// The label [`expansion_handler`](crate::db::AstDatabase::expansion_handler) reports
+            /// for this macro, like `builtin:concat`.
+            pub fn label(&self) -> &'static str {
+                match *self {
+                    $( BuiltinFnLikeExpander::$kind => concat!("builtin:", stringify!($name)), )*
+                }
+            }
         }
 
         impl EagerExpander {
@@ -49,6 +57,14 @@ pub fn expand(
                 };
                 expander(db, arg_id, tt)
             }
+
+            /// The label [`expansion_handler`](crate::db::AstDatabase::expansion_handler) reports
+            /// for this macro, like `builtin:concat`.
+            pub fn label(&self) -> &'static str {
+                match *self {
+                    $( EagerExpander::$e_kind => concat!("builtin:", stringify!($e_name)), )*
+                }
+            }
         }
 
         fn find_by_name(ident: &name::Name) -> Option<Either<BuiltinFnLikeExpander, EagerExpander>> {
@@ -74,6 +90,21 @@ fn new(subtree: tt::Subtree) -> Self {
     }
 }
 
+impl BuiltinFnLikeExpander {
+    /// Returns whether this is one of the inline assembly macros.
+    ///
+    /// Their arguments follow a grammar of their own, which we don't model. Instead of expanding
+    /// the operands, these macros expand to a stand-in which only keeps the template strings.
+    pub fn is_asm(&self) -> bool {
+        matches!(
+            self,
+            BuiltinFnLikeExpander::Asm
+                | BuiltinFnLikeExpander::LlvmAsm
+                | BuiltinFnLikeExpander::GlobalAsm
+        )
+    }
+}
+
 pub fn find_builtin_macro(
     ident: &name::Name,
 ) -> Option<Either<BuiltinFnLikeExpander, EagerExpander>> {
@@ -100,6 +131,8 @@ pub fn find_builtin_macro(
     (core_panic, CorePanic) => panic_expand,
     (std_panic, StdPanic) => panic_expand,
     (unreachable, Unreachable) => unreachable_expand,
+    (todo, Todo) => todo_expand,
+    (unimplemented, Unimplemented) => unimplemented_expand,
     (log_syntax, LogSyntax) => log_syntax_expand,
     (trace_macros, TraceMacros) => trace_macros_expand,
 
@@ -355,6 +388,50 @@ fn unreachable_expand(
     ExpandResult::ok(call)
 }
 
+fn todo_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> ExpandResult<tt::Subtree> {
+    panic_with_prefix_expand(db, id, tt, "not yet implemented")
+}
+
+fn unimplemented_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> ExpandResult<tt::Subtree> {
+    panic_with_prefix_expand(db, id, tt, "not implemented")
+}
+
+/// Expands to a `panic!` with the given message, followed by the formatted arguments if there are
+/// any, the way `todo!` and `unimplemented!` do.
+fn panic_with_prefix_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+    prefix: &str,
+) -> ExpandResult<tt::Subtree> {
+    let loc: MacroCallLoc = db.lookup_intern_macro_call(id);
+    // Expand to a macro call `$crate::panic::panic_{edition}`
+    let mut call = if db.crate_graph()[loc.krate].edition >= Edition::Edition2021 {
+        quote!(#DOLLAR_CRATE::panic::panic_2021!)
+    } else {
+        quote!(#DOLLAR_CRATE::panic::panic_2015!)
+    };
+
+    // The arguments are passed through as is, so that their tokens still map back to the call.
+    let args = if tt.token_trees.is_empty() {
+        quote!((#prefix))
+    } else {
+        let message = format!("{prefix}: {{}}");
+        let args = tt.clone();
+        quote!((#message, #DOLLAR_CRATE::format_args! #args))
+    };
+    call.token_trees.push(tt::TokenTree::Subtree(args));
+    ExpandResult::ok(call)
+}
+
 fn unquote_str(lit: &tt::Literal) -> Option<String> {
     let lit = ast::make::tokens::literal(&lit.to_string());
     let token = ast::String::cast(lit)?;
@@ -380,7 +457,7 @@ fn compile_error_expand(
 ) -> ExpandResult<ExpandedEager> {
     let err = match &*tt.token_trees {
         [tt::TokenTree::Leaf(tt::Leaf::Literal(it))] => match unquote_str(it) {
-            Some(unquoted) => ExpandError::Other(unquoted.into()),
+            Some(unquoted) => ExpandError::CompileError(unquoted.into()),
             None => ExpandError::Other("`compile_error!` argument must be a string".into()),
         },
         _ => ExpandError::Other("`compile_error!` argument must be a string".into()),
@@ -531,7 +608,9 @@ fn relative_file(
     path_str: &str,
     allow_recursion: bool,
 ) -> Result<FileId, ExpandError> {
-    let call_site = call_id.as_file().original_file(db);
+    let call_site = call_id.as_file().try_original_file(db).ok_or_else(|| {
+        ExpandError::Other(format!("cannot load `{path_str}` relative to a synthetic file").into())
+    })?;
     let path = AnchoredPath { anchor: call_site, path: path_str };
     let res = db
         .resolve_path(path)
@@ -1,22 +1,31 @@
 //! Defines database & queries for macro expansion.
 
-use std::sync::Arc;
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex},
+};
 
 use base_db::{salsa, SourceDatabase};
 use either::Either;
 use limit::Limit;
 use mbe::syntax_node_to_token_tree;
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
+use stdx::never;
 use syntax::{
     ast::{self, HasAttrs, HasDocComments},
-    AstNode, GreenNode, Parse, SyntaxNode, SyntaxToken, T,
+    AstNode, GreenNode, NodeOrToken, Parse, SourceFile, SyntaxElement, SyntaxNode, SyntaxToken,
+    TextSize, WalkEvent, T,
 };
 
 use crate::{
-    ast_id_map::AstIdMap, builtin_attr_macro::pseudo_derive_attr_expansion, fixup,
-    hygiene::HygieneFrame, BuiltinAttrExpander, BuiltinDeriveExpander, BuiltinFnLikeExpander,
-    ExpandError, ExpandResult, ExpandTo, HirFileId, HirFileIdRepr, MacroCallId, MacroCallKind,
-    MacroCallLoc, MacroDefId, MacroDefKind, MacroFile, ProcMacroExpander,
+    ast_id_map::AstIdMap,
+    builtin_attr_macro::pseudo_derive_attr_expansion,
+    fixup,
+    hygiene::HygieneFrame,
+    name::{Name, NameId},
+    pretty, BuiltinAttrExpander, BuiltinDeriveExpander, BuiltinFnLikeExpander, ExpandError,
+    ExpandResult, ExpandTo, HirFileId, HirFileIdRepr, MacroCallId, MacroCallKind, MacroCallLoc,
+    MacroDefId, MacroDefKind, MacroFile, ProcMacroExpander, SyntheticFileId,
 };
 
 /// Total limit on the number of tokens produced by any macro invocation.
@@ -27,6 +36,12 @@
 /// Actual max for `analysis-stats .` at some point: 30672.
 static TOKEN_LIMIT: Limit = Limit::new(524_288);
 
+/// Total limit on the number of syntax nodes the expansion of any macro invocation parses into.
+///
+/// Token counts miss deeply nested output, which produces several nodes per token, so expansions
+/// exceeding this limit are discarded with [`ExpandError::OutputTooLarge`] once parsed.
+static NODE_LIMIT: Limit = Limit::new(1_048_576);
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TokenExpander {
     /// Old-style `macro_rules` or the new macros 2.0
@@ -83,21 +98,40 @@ pub(crate) fn map_id_up(&self, id: tt::TokenId) -> (tt::TokenId, mbe::Origin) {
     }
 }
 
+/// The syntax tree of a hir file, as returned by [`AstDatabase::parse_or_expand_with_errors`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTree {
+    pub tree: SyntaxNode,
+    /// Whether parsing or expanding the file failed, in which case `tree` is
+    /// only partial.
+    pub had_errors: bool,
+}
+
 // FIXME: rename to ExpandDatabase
 #[salsa::query_group(AstDatabaseStorage)]
-pub trait AstDatabase: SourceDatabase {
+pub trait AstDatabase: SourceDatabase + HasExpansionProvenance {
     fn ast_id_map(&self, file_id: HirFileId) -> Arc<AstIdMap>;
 
     /// Main public API -- parses a hir file, not caring whether it's a real
     /// file or a macro expansion.
     #[salsa::transparent]
     fn parse_or_expand(&self, file_id: HirFileId) -> Option<SyntaxNode>;
+    /// Same as `parse_or_expand`, but also tells whether the tree is only a
+    /// best-effort result of error recovery.
+    #[salsa::transparent]
+    fn parse_or_expand_with_errors(&self, file_id: HirFileId) -> Option<ParsedTree>;
     /// Implementation for the macro case.
     fn parse_macro_expansion(
         &self,
         macro_file: MacroFile,
     ) -> ExpandResult<Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)>>;
 
+    /// Text of a synthetic file, like an injected prelude import.
+    #[salsa::input]
+    fn synthetic_file_text(&self, file_id: SyntheticFileId) -> Arc<str>;
+    /// Implementation for the synthetic file case.
+    fn parse_synthetic_file(&self, file_id: SyntheticFileId) -> Parse<SourceFile>;
+
     /// Macro ids. That's probably the tricksiest bit in rust-analyzer, and the
     /// reason why we use salsa at all.
     ///
@@ -106,6 +140,10 @@ fn parse_macro_expansion(
     #[salsa::interned]
     fn intern_macro_call(&self, macro_call: MacroCallLoc) -> MacroCallId;
 
+    /// Names, interned so that they can be keyed on by a `u32`-sized id.
+    #[salsa::interned]
+    fn intern_name(&self, name: Name) -> NameId;
+
     /// Lowers syntactic macro call to a token tree representation.
     #[salsa::transparent]
     fn macro_arg(
@@ -131,6 +169,211 @@ fn macro_arg(
     fn macro_expand_error(&self, macro_call: MacroCallId) -> Option<ExpandError>;
 
     fn hygiene_frame(&self, file_id: HirFileId) -> Arc<HygieneFrame>;
+
+    /// Names the builtin expander that handles the macro call, like `builtin:concat`. This is
+    /// meant for debugging calls that resolve to an unexpected kind of macro.
+    ///
+    /// Returns `None` for calls to declarative and procedural macros.
+    fn expansion_handler(&self, id: MacroCallId) -> Option<&'static str>;
+}
+
+/// The `macro_rules!` arm a macro call got expanded with, see [`explain_macro_match`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchResult {
+    pub arm_index: usize,
+    /// What each metavariable of the arm was bound to, sorted by name.
+    pub bindings: Vec<(Name, tt::Subtree)>,
+}
+
+/// Explains the expansion of a call to a declarative macro by returning which of its arms matched
+/// the call's arguments, and what each metavariable was bound to. If no arm matched, returns the
+/// arm that came closest with the reason it failed to match instead.
+///
+/// Returns `None` for calls to other kinds of macros.
+pub fn explain_macro_match(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+) -> Option<Result<MatchResult, mbe::NoMatchingArm>> {
+    let loc = db.lookup_intern_macro_call(id);
+    let expander = db.macro_def(loc.def).ok()?;
+    let mac = match &*expander {
+        TokenExpander::DeclarativeMacro { mac, .. } => mac,
+        _ => return None,
+    };
+    let macro_arg = db.macro_arg(id)?;
+    let res = mac.match_arm(&macro_arg.0).map(|mbe::MatchedArm { index, bindings }| MatchResult {
+        arm_index: index,
+        bindings: bindings.into_iter().map(|(name, tt)| (Name::resolve(&name), tt)).collect(),
+    });
+    Some(res)
+}
+
+/// Expands the macro call `id` and renders the resulting token tree, without parsing it back into
+/// a syntax tree.
+///
+/// The tree is printed like the expansions in `hir_def`'s macro expansion tests, see
+/// [`pretty::print_subtree`]. This is meant for snapshotting what the expanders produce in tests.
+pub fn expand_to_subtree_text(db: &dyn AstDatabase, id: MacroCallId) -> String {
+    let ExpandResult { value, err } = db.macro_expand(id);
+    let text = value.map_or_else(String::new, |tt| pretty::print_subtree(&tt));
+    match err {
+        Some(err) => format!("/* error: {err} */{text}"),
+        None => text,
+    }
+}
+
+fn expansion_handler(db: &dyn AstDatabase, id: MacroCallId) -> Option<&'static str> {
+    let loc = db.lookup_intern_macro_call(id);
+    match loc.def.kind {
+        MacroDefKind::BuiltIn(it, _) => Some(it.label()),
+        MacroDefKind::BuiltInAttr(it, _) => Some(it.label()),
+        MacroDefKind::BuiltInDerive(it, _) => Some(it.label()),
+        MacroDefKind::BuiltInEager(it, _) => Some(it.label()),
+        MacroDefKind::Declarative(_) | MacroDefKind::ProcMacro(..) => None,
+    }
+}
+
+/// Marks the expansions done on this thread, until the returned guard is dropped, as having been
+/// triggered for `reason` (e.g. "hover" or "name resolution"), see [`expansion_provenance`].
+///
+/// Reasons nest: an expansion done while several reasons are active records all of them.
+pub fn enter_expansion_reason(reason: impl Into<String>) -> ExpansionReason {
+    EXPANSION_REASONS.with(|reasons| reasons.borrow_mut().push(reason.into()));
+    ExpansionReason { _priv: () }
+}
+
+#[must_use]
+pub struct ExpansionReason {
+    _priv: (),
+}
+
+impl Drop for ExpansionReason {
+    fn drop(&mut self) {
+        EXPANSION_REASONS.with(|reasons| {
+            never!(reasons.borrow_mut().pop().is_none(), "unbalanced expansion reasons");
+        });
+    }
+}
+
+thread_local! {
+    static EXPANSION_REASONS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Expansions accessed while a reason was active, with the reasons that were active at the time.
+///
+/// This is a debugging aid and lives outside of salsa's storage: [`MacroFile`]s are interned per
+/// database, so each database owns its log, and databases clear it whenever their inputs change.
+#[derive(Debug, Default)]
+pub struct ExpansionProvenanceLog {
+    expansions: Mutex<FxHashMap<MacroFile, String>>,
+}
+
+impl ExpansionProvenanceLog {
+    pub fn clear(&self) {
+        self.expansions.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+}
+
+/// Gives access to the database's [`ExpansionProvenanceLog`].
+pub trait HasExpansionProvenance {
+    fn expansion_provenance_log(&self) -> &ExpansionProvenanceLog;
+}
+
+/// Records the active reasons for an access to the expansion of `macro_file`. This must not be
+/// called from within a query, as it would only be recorded when the query is recomputed.
+pub(crate) fn record_expansion_provenance(db: &dyn AstDatabase, macro_file: MacroFile) {
+    let reasons = EXPANSION_REASONS.with(|reasons| reasons.borrow().join(" > "));
+    if reasons.is_empty() {
+        return;
+    }
+    let log = db.expansion_provenance_log();
+    log.expansions.lock().unwrap_or_else(|err| err.into_inner()).insert(macro_file, reasons);
+}
+
+/// Returns why the expansion of `macro_file` was last asked for, that is the reasons entered via
+/// [`enter_expansion_reason`] at the time, from the outermost to the innermost. Accesses served
+/// from salsa's cache count as well.
+///
+/// Returns `None` if no reason was ever active during an access.
+pub fn expansion_provenance(db: &dyn AstDatabase, macro_file: MacroFile) -> Option<String> {
+    let log = db.expansion_provenance_log();
+    log.expansions.lock().unwrap_or_else(|err| err.into_inner()).get(&macro_file).cloned()
+}
+
+/// Checks whether the macro files `a` and `b` expanded to the same syntax tree, ignoring trivia.
+///
+/// This is useful to tell whether an edit that caused a call to be re-expanded actually changed
+/// its expansion. Files that failed to expand to a tree are never equal to anything, not even to
+/// themselves.
+pub fn expansions_equal(db: &dyn AstDatabase, a: MacroFile, b: MacroFile) -> bool {
+    // Token `Leave` events carry no information, node ones delimit the children.
+    fn significant(node: SyntaxNode) -> impl Iterator<Item = WalkEvent<SyntaxElement>> {
+        node.preorder_with_tokens().filter(|event| match event {
+            WalkEvent::Enter(NodeOrToken::Token(it)) => !it.kind().is_trivia(),
+            WalkEvent::Leave(it) => it.as_node().is_some(),
+            WalkEvent::Enter(NodeOrToken::Node(_)) => true,
+        })
+    }
+
+    let (tree_a, tree_b) = match (db.parse_or_expand(a.into()), db.parse_or_expand(b.into())) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return false,
+    };
+    if a == b {
+        return true;
+    }
+    stdx::iter_eq_by(significant(tree_a), significant(tree_b), |a, b| match (a, b) {
+        (WalkEvent::Enter(NodeOrToken::Node(a)), WalkEvent::Enter(NodeOrToken::Node(b))) => {
+            a.kind() == b.kind()
+        }
+        (WalkEvent::Enter(NodeOrToken::Token(a)), WalkEvent::Enter(NodeOrToken::Token(b))) => {
+            a.kind() == b.kind() && a.text() == b.text()
+        }
+        (WalkEvent::Leave(_), WalkEvent::Leave(_)) => true,
+        _ => false,
+    })
+}
+
+/// Like [`expand_speculative`], but with the edit to the arguments of a fn-like macro call given as
+/// text: the arguments are expanded as if `replacement` was inserted into them at `offset`, which is
+/// relative to the file containing the call.
+///
+/// Returns the expansion, and the cursor, placed right after the inserted text, mapped into it.
+/// Returns `None` if `offset` does not fall on a char boundary inside the arguments.
+pub fn expand_speculative_edit(
+    db: &dyn AstDatabase,
+    actual_macro_call: MacroCallId,
+    replacement: &str,
+    offset: TextSize,
+) -> Option<(SyntaxNode, TextSize)> {
+    let loc = db.lookup_intern_macro_call(actual_macro_call);
+    let call = match loc.kind {
+        MacroCallKind::FnLike { ast_id, .. } => ast_id.to_node(db),
+        _ => return None,
+    };
+    let args = call.token_tree()?;
+    let args_range = args.syntax().text_range();
+    // The edit has to land between the delimiters.
+    if offset <= args_range.start() || offset >= args_range.end() {
+        return None;
+    }
+
+    let mut text = args.syntax().text().to_string();
+    let insert_at = (offset - args_range.start()).into();
+    if !text.is_char_boundary(insert_at) {
+        return None;
+    }
+    text.insert_str(insert_at, replacement);
+    const PREFIX: &str = "m!";
+    let parse = SourceFile::parse(&format!("{PREFIX}{text}"));
+    let speculative_args = parse.tree().syntax().descendants().find_map(ast::TokenTree::cast)?;
+    let cursor = TextSize::of(PREFIX) + (offset - args_range.start()) + TextSize::of(replacement);
+    let token = speculative_args.syntax().token_at_offset(cursor).left_biased()?;
+
+    let (node, mapped) =
+        expand_speculative(db, actual_macro_call, speculative_args.syntax(), token.clone())?;
+    let in_token = (cursor - token.text_range().start()).min(mapped.text_range().len());
+    Some((node, mapped.text_range().start() + in_token))
 }
 
 /// This expands the given macro call, but with different arguments. This is
@@ -240,16 +483,43 @@ fn ast_id_map(db: &dyn AstDatabase, file_id: HirFileId) -> Arc<AstIdMap> {
 }
 
 fn parse_or_expand(db: &dyn AstDatabase, file_id: HirFileId) -> Option<SyntaxNode> {
+    db.parse_or_expand_with_errors(file_id).map(|it| it.tree)
+}
+
+fn parse_or_expand_with_errors(db: &dyn AstDatabase, file_id: HirFileId) -> Option<ParsedTree> {
     match file_id.repr() {
-        HirFileIdRepr::FileId(file_id) => Some(db.parse(file_id).tree().syntax().clone()),
+        HirFileIdRepr::FileId(file_id) => {
+            let parse = db.parse(file_id);
+            // Hand out the very tree of `parse`, not a reparse, so that anything keyed on its
+            // nodes keeps working for real files.
+            Some(ParsedTree {
+                tree: parse.tree().syntax().clone(),
+                had_errors: !parse.errors().is_empty(),
+            })
+        }
         HirFileIdRepr::MacroFile(macro_file) => {
-            // FIXME: Note how we convert from `Parse` to `SyntaxNode` here,
-            // forgetting about parse errors.
-            db.parse_macro_expansion(macro_file).value.map(|(it, _)| it.syntax_node())
+            record_expansion_provenance(db, macro_file);
+            let ExpandResult { value, err } = db.parse_macro_expansion(macro_file);
+            value.map(|(parse, _)| ParsedTree {
+                had_errors: err.is_some() || !parse.errors().is_empty(),
+                tree: parse.syntax_node(),
+            })
+        }
+        HirFileIdRepr::SyntheticFile(file_id) => {
+            let parse = db.parse_synthetic_file(file_id);
+            Some(ParsedTree {
+                tree: parse.tree().syntax().clone(),
+                had_errors: !parse.errors().is_empty(),
+            })
         }
     }
 }
 
+fn parse_synthetic_file(db: &dyn AstDatabase, file_id: SyntheticFileId) -> Parse<SourceFile> {
+    let _p = profile::span("parse_synthetic_file");
+    SourceFile::parse(&db.synthetic_file_text(file_id))
+}
+
 fn parse_macro_expansion(
     db: &dyn AstDatabase,
     macro_file: MacroFile,
@@ -288,8 +558,16 @@ fn parse_macro_expansion(
     tracing::debug!("expanded = {}", tt.as_debug_string());
     tracing::debug!("kind = {:?}", expand_to);
 
+    // Note that identical expansions of different calls are deliberately *not* deduplicated into
+    // one green tree: syntax nodes compare equal when they share their green node and offset, so
+    // the roots of such expansions would be indistinguishable, and consumers mapping roots back to
+    // their `HirFileId` (like `Semantics`) would mix the files up.
     let (parse, rev_token_map) = token_tree_to_syntax_node(&tt, expand_to);
 
+    if exceeds_node_limit(&parse.syntax_node(), NODE_LIMIT.inner()) {
+        return ExpandResult::only_err(ExpandError::OutputTooLarge(NODE_LIMIT.inner()));
+    }
+
     ExpandResult { value: Some((parse, Arc::new(rev_token_map))), err: result.err }
 }
 
@@ -428,6 +706,14 @@ fn macro_expand(db: &dyn AstDatabase, id: MacroCallId) -> ExpandResult<Option<Ar
         };
     }
 
+    // Proc macros without an expander can't produce anything, so don't bother lowering their
+    // arguments.
+    if let MacroDefKind::ProcMacro(expander, ..) = loc.def.kind {
+        if expander.is_dummy() {
+            return ExpandResult::only_err(ExpandError::UnresolvedProcMacro(loc.def.krate));
+        }
+    }
+
     let macro_arg = match db.macro_arg(id) {
         Some(it) => it,
         None => {
@@ -464,9 +750,41 @@ fn macro_expand(db: &dyn AstDatabase, id: MacroCallId) -> ExpandResult<Option<Ar
 
     fixup::reverse_fixups(&mut tt, &macro_arg.1, &macro_arg.2);
 
+    // Builtin derives only ever produce items, so just check the output of proc-macro derives.
+    let err = match err {
+        None if matches!(loc.kind, MacroCallKind::Derive { .. })
+            && matches!(loc.def.kind, MacroDefKind::ProcMacro(..))
+            && !is_only_items(&tt) =>
+        {
+            Some(ExpandError::DeriveOutputNotItems)
+        }
+        err => err,
+    };
+
     ExpandResult { value: Some(Arc::new(tt)), err }
 }
 
+/// Whether `tt` parses as a sequence of items, and nothing else.
+fn is_only_items(tt: &tt::Subtree) -> bool {
+    let (parse, _) = token_tree_to_syntax_node(tt, ExpandTo::Items);
+    parse.syntax_node().children().all(|it| ast::Item::can_cast(it.kind()))
+}
+
+/// Counts the nodes of the green tree, which unlike the syntax tree does not need to be built up.
+fn exceeds_node_limit(node: &SyntaxNode, limit: usize) -> bool {
+    let green = node.green();
+    let mut stack = vec![&*green];
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        count += 1;
+        if count > limit {
+            return true;
+        }
+        stack.extend(node.children().filter_map(NodeOrToken::into_node));
+    }
+    false
+}
+
 fn macro_expand_error(db: &dyn AstDatabase, macro_call: MacroCallId) -> Option<ExpandError> {
     db.macro_expand(macro_call).err
 }
@@ -519,3 +837,330 @@ fn token_tree_to_syntax_node(
     };
     mbe::token_tree_to_syntax_node(tt, entry_point)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use base_db::{fixture::WithFixture, SourceDatabase, SourceDatabaseExt};
+    use expect_test::expect;
+    use syntax::{ast, AstNode, SyntaxKind, SyntaxNode, TextSize};
+
+    use crate::{
+        test_db::TestDB, ExpandTo, HirFileId, MacroCallId, MacroCallKind, MacroFile,
+        SyntheticFileId,
+    };
+
+    use super::{
+        enter_expansion_reason, exceeds_node_limit, expand_speculative_edit,
+        expand_to_subtree_text, expansion_provenance, expansions_equal, explain_macro_match,
+        AstDatabase,
+    };
+
+    fn parse_items(text: &str) -> SyntaxNode {
+        let (tt, _) = mbe::parse_to_token_tree(text).unwrap();
+        mbe::token_tree_to_syntax_node(&tt, mbe::TopEntryPoint::MacroItems).0.syntax_node()
+    }
+
+    #[test]
+    fn deeply_nested_expansions_exceed_node_limit() {
+        assert!(!exceeds_node_limit(&parse_items("fn f() {}"), 16));
+        // Only a handful of tokens, but two nodes for every block.
+        let nested = parse_items("fn g() { { { { { { { { { { { { } } } } } } } } } } } }");
+        assert!(exceeds_node_limit(&nested, 16));
+        assert!(!exceeds_node_limit(&nested, 64));
+    }
+
+    #[test]
+    fn partial_expansion_reports_errors() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! good {
+    () => { fn f() {} };
+}
+macro_rules! bad {
+    () => { fn g() -> {} };
+}
+good!();
+bad!();
+"#,
+        );
+        let had_errors: Vec<_> = db
+            .macro_calls(file_id)
+            .into_iter()
+            .map(|(_, id)| db.parse_or_expand_with_errors(id.as_file()).unwrap().had_errors)
+            .collect();
+        assert_eq!(had_errors, [false, true]);
+    }
+
+    #[test]
+    fn explain_matched_macro_arm() {
+        fn leaves(tt: &tt::Subtree) -> Vec<String> {
+            tt.token_trees
+                .iter()
+                .flat_map(|it| match it {
+                    tt::TokenTree::Leaf(leaf) => vec![leaf.to_string()],
+                    tt::TokenTree::Subtree(subtree) => leaves(subtree),
+                })
+                .collect()
+        }
+
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($i:ident) => { struct $i; };
+    ($e:expr, $($r:tt)*) => { const _: () = (); };
+}
+m!(Foo);
+m!(1 + 1, a b);
+m!(Foo Bar);
+"#,
+        );
+        let ids: Vec<_> = db.macro_calls(file_id).into_iter().map(|(_, id)| id).collect();
+        assert_eq!(ids.len(), 3);
+
+        let ident = explain_macro_match(&db, ids[0]).unwrap().unwrap();
+        assert_eq!(ident.arm_index, 0);
+        let bindings: Vec<_> =
+            ident.bindings.iter().map(|(name, tt)| (name.to_string(), leaves(tt))).collect();
+        assert_eq!(bindings, [("i".to_string(), vec!["Foo".to_string()])]);
+
+        let expr = explain_macro_match(&db, ids[1]).unwrap().unwrap();
+        assert_eq!(expr.arm_index, 1);
+        let bindings: Vec<_> =
+            expr.bindings.iter().map(|(name, tt)| (name.to_string(), leaves(tt))).collect();
+        assert_eq!(
+            bindings,
+            [
+                ("e".to_string(), vec!["1".to_string(), "+".to_string(), "1".to_string()]),
+                ("r".to_string(), vec!["a".to_string(), "b".to_string()]),
+            ]
+        );
+        // One group per repetition.
+        assert_eq!(expr.bindings[1].1.token_trees.len(), 2);
+
+        // `Foo` matches `$i:ident`, but `Bar` is left over.
+        let no_match = explain_macro_match(&db, ids[2]).unwrap().unwrap_err();
+        assert_eq!(no_match.closest_index, Some(0));
+        assert_eq!(no_match.err, mbe::ExpandError::LeftoverTokens);
+    }
+
+    #[test]
+    fn expansion_records_active_reasons() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    () => { struct S; };
+}
+m!();
+"#,
+        );
+        let (_, id) = db.macro_calls(file_id).pop().unwrap();
+        let macro_file = id.as_file().macro_file().unwrap();
+        {
+            let _outer = enter_expansion_reason("test");
+            let _inner = enter_expansion_reason("name resolution");
+            db.parse_or_expand(id.as_file()).unwrap();
+        }
+        assert_eq!(
+            expansion_provenance(&db, macro_file).as_deref(),
+            Some("test > name resolution")
+        );
+
+        // Accessing the cached expansion records the new reason...
+        {
+            let _reason = enter_expansion_reason("hover");
+            db.parse_or_expand(id.as_file()).unwrap();
+        }
+        assert_eq!(expansion_provenance(&db, macro_file).as_deref(), Some("hover"));
+
+        // ...while accesses without any reason keep the last one.
+        db.parse_or_expand(id.as_file()).unwrap();
+        assert_eq!(expansion_provenance(&db, macro_file).as_deref(), Some("hover"));
+    }
+
+    #[test]
+    fn parse_or_expand_reuses_real_file_parse() {
+        let (db, file_id) = TestDB::with_single_file("struct S;");
+        let parse = db.parse(file_id).syntax_node();
+        let tree = db.parse_or_expand(file_id.into()).unwrap();
+        assert_eq!(tree, parse);
+        assert!(std::ptr::eq(&*tree.green(), &*parse.green()));
+    }
+
+    #[test]
+    fn expand_to_subtree_text_snapshots() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! f {
+    ($name:ident) => { fn $name() -> u32 { 1 + 1 } };
+}
+macro_rules! lets {
+    ($($i:ident),*) => { $(let $i = "s";)* };
+}
+macro_rules! puncts {
+    () => { fn f<'a>(x: &'a u8) -> bool { x == &0 } };
+}
+f!(foo);
+lets!(a, b);
+puncts!();
+"#,
+        );
+        let texts: Vec<_> = db
+            .macro_calls(file_id)
+            .into_iter()
+            .map(|(_, id)| expand_to_subtree_text(&db, id))
+            .collect();
+        expect![[r#"
+            fn foo() -> u32 {
+                1+1
+            }
+
+            let a = "s";
+            let b = "s";
+
+            fn f<'a>(x:&'a u8) -> bool {
+                x==&0
+            }
+        "#]]
+        .assert_eq(&format!("{}\n", texts.join("\n\n")));
+    }
+
+    #[test]
+    fn expansions_of_different_kinds_are_cached_separately() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    () => { foo() };
+}
+m!();
+"#,
+        );
+        let (_, items_id) = db.macro_calls(file_id).pop().unwrap();
+        let mut loc = db.lookup_intern_macro_call(items_id);
+        let ast_id = match loc.kind {
+            MacroCallKind::FnLike { ast_id, expand_to: ExpandTo::Items } => ast_id,
+            kind => panic!("unexpected call kind {kind:?}"),
+        };
+        loc.kind = MacroCallKind::FnLike { ast_id, expand_to: ExpandTo::Expr };
+        let expr_id = db.intern_macro_call(loc);
+        assert_ne!(items_id, expr_id);
+
+        let root_kind = |id: MacroCallId| db.parse_or_expand(id.as_file()).unwrap().kind();
+        assert_eq!(root_kind(items_id), SyntaxKind::MACRO_ITEMS);
+        assert_eq!(root_kind(expr_id), SyntaxKind::CALL_EXPR);
+        assert_eq!(root_kind(items_id), SyntaxKind::MACRO_ITEMS);
+    }
+
+    #[test]
+    fn speculative_edit_maps_cursor_into_expansion() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($($tt:tt)*) => { fn f() { $($tt)* } };
+}
+m!(let x = ;);
+"#,
+        );
+        let (_, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        let text = db.file_text(file_id);
+        let offset = TextSize::try_from(text.find(" ;)").unwrap() + 1).unwrap();
+
+        let (expanded, cursor) = expand_speculative_edit(&db, macro_call_id, "fo", offset).unwrap();
+        let token = expanded.token_at_offset(cursor).left_biased().unwrap();
+        assert_eq!(token.text(), "fo");
+        assert_eq!(token.text_range().end(), cursor);
+
+        // The call's actual expansion is unaffected.
+        assert!(!db.parse_or_expand(macro_call_id.as_file()).unwrap().to_string().contains("fo"));
+    }
+
+    #[test]
+    fn speculative_edit_rejects_offset_inside_char() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($($tt:tt)*) => { fn f() { $($tt)* } };
+}
+m!("é");
+"#,
+        );
+        let (_, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        let text = db.file_text(file_id);
+        let offset = TextSize::try_from(text.find('é').unwrap() + 1).unwrap();
+
+        assert!(expand_speculative_edit(&db, macro_call_id, "fo", offset).is_none());
+    }
+
+    #[test]
+    fn synthetic_files_parse_and_get_ast_ids() {
+        let mut db = TestDB::with_files("struct Unrelated;");
+        let synthetic = SyntheticFileId(0);
+        db.set_synthetic_file_text(
+            synthetic,
+            Arc::from("use std::prelude::rust_2021::*;\nstruct S;"),
+        );
+        let file_id = HirFileId::from(synthetic);
+        assert_eq!(file_id.synthetic_file(), Some(synthetic));
+        assert!(!file_id.is_macro());
+        assert_eq!(file_id.try_original_file(&db), None);
+
+        let root = db.parse_or_expand(file_id).unwrap();
+        let ast_id_map = db.ast_id_map(file_id);
+        let items: Vec<_> = root.descendants().filter_map(ast::Item::cast).collect();
+        assert_eq!(items.len(), 2);
+        for item in items {
+            let ptr = ast_id_map.get(ast_id_map.ast_id(&item));
+            assert_eq!(ptr.to_node(&root), item);
+        }
+    }
+
+    #[test]
+    fn identical_expansions_keep_distinct_trees() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    () => { struct S; };
+}
+m!();
+m!();
+"#,
+        );
+        let roots: Vec<_> = db
+            .macro_calls(file_id)
+            .into_iter()
+            .map(|(_, id)| db.parse_or_expand(id.as_file()).unwrap())
+            .collect();
+        assert_eq!(roots[0].to_string(), roots[1].to_string());
+        // Sharing the green tree would make the roots compare equal, see `parse_macro_expansion`.
+        assert_ne!(roots[0], roots[1]);
+    }
+
+    #[test]
+    fn whitespace_edits_keep_expansions_equal() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($($tt:tt)*) => { fn f() { $($tt)* } };
+}
+m!(1 + 2);
+m!(1+2   );
+m!(1 + 3);
+macro_rules! broken {
+    ($x) => {};
+}
+broken!();
+"#,
+        );
+        let files: Vec<_> = db
+            .macro_calls(file_id)
+            .into_iter()
+            .map(|(_, macro_call_id)| MacroFile { macro_call_id })
+            .collect();
+        assert!(expansions_equal(&db, files[0], files[0]));
+        assert!(expansions_equal(&db, files[0], files[1]));
+        assert!(!expansions_equal(&db, files[0], files[2]));
+        // Failed expansions are never equal, not even to themselves.
+        assert!(!expansions_equal(&db, files[3], files[3]));
+    }
+}
@@ -71,6 +71,41 @@ pub fn local_inner_macros(&self, db: &dyn AstDatabase, path: ast::Path) -> Optio
     }
 }
 
+/// The segments of a path, each alongside the place it was written at.
+///
+/// Within macro expansions, the segments of a single path may originate from different places:
+/// in `$crate::foo`, the `$crate` is written in the macro definition, while `foo` might have been
+/// passed in by the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitPath {
+    segments: Vec<(ast::PathSegment, InFile<TextRange>)>,
+}
+
+impl SplitPath {
+    pub fn new(db: &dyn AstDatabase, path: InFile<&ast::Path>) -> SplitPath {
+        let frames = HygieneFrames::new(db, path.file_id);
+        let segments = path
+            .value
+            .segments()
+            .map(|segment| {
+                let range = segment
+                    .syntax()
+                    .first_token()
+                    .map_or_else(|| segment.syntax().text_range(), |it| it.text_range());
+                let source = frames.source_of(db, InFile::new(path.file_id, range));
+                (segment, source)
+            })
+            .collect();
+        SplitPath { segments }
+    }
+
+    /// Returns the segments of the path, each with the range of its first token in the file it
+    /// was written in.
+    pub fn segments(&self) -> impl Iterator<Item = (&ast::PathSegment, InFile<TextRange>)> + '_ {
+        self.segments.iter().map(|(segment, source)| (segment, *source))
+    }
+}
+
 #[derive(Clone, Debug)]
 struct HygieneFrames(Arc<HygieneFrame>);
 
@@ -119,6 +154,32 @@ fn root_crate(&self, db: &dyn AstDatabase, node: &SyntaxNode) -> Option<CrateId>
 
         result
     }
+
+    /// Maps the range of an identifier up through the expansions it came from, until reaching the
+    /// place it was written at.
+    fn source_of(&self, db: &dyn AstDatabase, mut source: InFile<TextRange>) -> InFile<TextRange> {
+        let mut current = self.0.clone();
+
+        while let Some((mapped, origin)) =
+            current.expansion.as_ref().and_then(|it| it.map_ident_up(db, source.value))
+        {
+            source = mapped;
+
+            let site = match origin {
+                Origin::Def => &current.def_site,
+                Origin::Call => &current.call_site,
+            };
+
+            let site = match site {
+                None => break,
+                Some(it) => it,
+            };
+
+            current = site.clone();
+        }
+
+        source
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -254,3 +315,44 @@ pub(crate) fn new(db: &dyn AstDatabase, file_id: HirFileId) -> HygieneFrame {
         HygieneFrame { expansion: Some(info), local_inner, krate, call_site, def_site }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use base_db::{fixture::WithFixture, SourceDatabaseExt};
+    use syntax::{ast, AstNode};
+
+    use crate::{db::AstDatabase, test_db::TestDB, InFile};
+
+    use super::SplitPath;
+
+    #[test]
+    fn split_path_segments_keep_their_own_source() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($tail:ident) => { use $crate::$tail; };
+}
+m!(foo);
+"#,
+        );
+        let (_, id) = db.macro_calls(file_id).pop().unwrap();
+        let expansion = db.parse_or_expand(id.as_file()).unwrap();
+        let path = expansion.descendants().find_map(ast::Path::cast).unwrap();
+        let path = path.syntax().ancestors().filter_map(ast::Path::cast).last().unwrap();
+
+        let split = SplitPath::new(&db, InFile::new(id.as_file(), &path));
+        let sources: Vec<_> = split.segments().map(|(_, source)| source).collect();
+        assert_eq!(sources.len(), 2);
+
+        let text = db.file_text(file_id);
+        let call = text.find("m!(foo)").unwrap();
+        // `$crate` was written in the macro definition...
+        assert_eq!(sources[0].file_id, file_id.into());
+        assert_eq!(&text[sources[0].value], "crate");
+        assert!(usize::from(sources[0].value.end()) < call);
+        // ...while `foo` was written by the caller.
+        assert_eq!(sources[1].file_id, file_id.into());
+        assert_eq!(&text[sources[1].value], "foo");
+        assert!(usize::from(sources[1].value.start()) > call);
+    }
+}
@@ -17,7 +17,10 @@
 pub mod quote;
 pub mod eager;
 pub mod mod_path;
+pub mod pretty;
 mod fixup;
+#[cfg(test)]
+mod test_db;
 
 pub use mbe::{Origin, ValueResult};
 
@@ -29,10 +32,13 @@
     CrateId, FileId, FileRange, ProcMacroKind,
 };
 use either::Either;
+use itertools::Itertools;
+use smallvec::{smallvec, SmallVec};
 use syntax::{
     algo::{self, skip_trivia_token},
-    ast::{self, AstNode, HasDocComments},
-    Direction, SyntaxNode, SyntaxToken,
+    ast::{self, AstNode, HasDocComments, HasName},
+    Direction, NodeOrToken, SyntaxKind, SyntaxNode, SyntaxNodePtr, SyntaxToken, TextRange,
+    TextSize,
 };
 
 use crate::{
@@ -42,6 +48,7 @@
     builtin_fn_macro::{BuiltinFnLikeExpander, EagerExpander},
     db::TokenExpander,
     mod_path::ModPath,
+    name::{AsName, Name},
     proc_macro::ProcMacroExpander,
 };
 
@@ -51,6 +58,14 @@
 pub enum ExpandError {
     UnresolvedProcMacro(CrateId),
     Mbe(mbe::ExpandError),
+    /// A derive macro expanded to something other than items, which is a bug in its expander.
+    DeriveOutputNotItems,
+    /// A `compile_error!` invocation, carrying its message.
+    CompileError(Box<str>),
+    /// The expansion parsed into more syntax nodes than the limit it carries.
+    OutputTooLarge(usize),
+    /// Macro calls nested inside expansions went past the expansion depth limit.
+    RecursionLimitExceeded,
     Other(Box<str>),
 }
 
@@ -65,7 +80,14 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExpandError::UnresolvedProcMacro(_) => f.write_str("unresolved proc-macro"),
             ExpandError::Mbe(it) => it.fmt(f),
-            ExpandError::Other(it) => f.write_str(it),
+            ExpandError::DeriveOutputNotItems => {
+                f.write_str("derive macro expanded to something other than items")
+            }
+            ExpandError::OutputTooLarge(limit) => {
+                write!(f, "macro invocation exceeds node limit: produces more than {limit} nodes")
+            }
+            ExpandError::RecursionLimitExceeded => f.write_str("macro expansion is too deep"),
+            ExpandError::CompileError(it) | ExpandError::Other(it) => f.write_str(it),
         }
     }
 }
@@ -84,12 +106,23 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 /// (`MacroCallId` uses the location interning. You can check details here:
 /// <https://en.wikipedia.org/wiki/String_interning>).
 ///
-/// The two variants are encoded in a single u32 which are differentiated by the MSB.
-/// If the MSB is 0, the value represents a `FileId`, otherwise the remaining 31 bits represent a
-/// `MacroCallId`.
+/// Lastly, a `HirFileId` may be a [`SyntheticFileId`], for code that is neither written by the user
+/// nor produced by a macro call.
+///
+/// The variants are encoded in a single u32 which are differentiated by the two most significant
+/// bits. If the MSB is 1, the remaining 31 bits represent a `MacroCallId`. Otherwise, if the second
+/// MSB is 1, the remaining 30 bits represent a `SyntheticFileId`, and if it is 0 as well, the value
+/// represents a `FileId`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HirFileId(u32);
 
+/// Identifies a synthetic file, whose code is injected by the analysis itself, like an implicit
+/// prelude import. The text of these files is set with
+/// [`AstDatabase::set_synthetic_file_text`][db::AstDatabase::set_synthetic_file_text].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SyntheticFileId(pub u32);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MacroFile {
     pub macro_call_id: MacroCallId,
@@ -116,7 +149,12 @@ pub struct MacroDefId {
     pub local_inner: bool,
 }
 
+/// What kind of definition a macro comes from.
+///
+/// More kinds will be added over time, so outside of this crate prefer the `is_*` accessors over
+/// matching on the variants.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum MacroDefKind {
     Declarative(AstId<ast::Macro>),
     BuiltIn(BuiltinFnLikeExpander, AstId<ast::Macro>),
@@ -126,6 +164,33 @@ pub enum MacroDefKind {
     ProcMacro(ProcMacroExpander, ProcMacroKind, AstId<ast::Fn>),
 }
 
+impl MacroDefKind {
+    /// Returns whether this is a user-written `macro_rules!` or `macro` definition.
+    pub fn is_declarative(&self) -> bool {
+        matches!(self, MacroDefKind::Declarative(_))
+    }
+
+    /// Returns whether this macro is implemented by rust-analyzer itself.
+    pub fn is_builtin(&self) -> bool {
+        matches!(
+            self,
+            MacroDefKind::BuiltIn(..)
+                | MacroDefKind::BuiltInAttr(..)
+                | MacroDefKind::BuiltInDerive(..)
+                | MacroDefKind::BuiltInEager(..)
+        )
+    }
+
+    /// Returns whether this is an eagerly expanded builtin macro, like `concat!`.
+    pub fn is_eager(&self) -> bool {
+        matches!(self, MacroDefKind::BuiltInEager(..))
+    }
+
+    pub fn is_proc_macro(&self) -> bool {
+        matches!(self, MacroDefKind::ProcMacro(..))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct EagerCallInfo {
     /// NOTE: This can be *either* the expansion result, *or* the argument to the eager macro!
@@ -166,6 +231,7 @@ pub enum MacroCallKind {
 enum HirFileIdRepr {
     FileId(FileId),
     MacroFile(MacroFile),
+    SyntheticFile(SyntheticFileId),
 }
 
 impl From<FileId> for HirFileId {
@@ -178,22 +244,44 @@ fn from(FileId(id): FileId) -> Self {
 impl From<MacroFile> for HirFileId {
     fn from(MacroFile { macro_call_id: MacroCallId(id) }: MacroFile) -> Self {
         let id = id.as_u32();
-        assert!(id < Self::MAX_FILE_ID);
+        assert!(id < Self::MAX_MACRO_FILE_ID);
         HirFileId(id | Self::MACRO_FILE_TAG_MASK)
     }
 }
 
+impl From<SyntheticFileId> for HirFileId {
+    fn from(SyntheticFileId(id): SyntheticFileId) -> Self {
+        assert!(id < Self::MAX_FILE_ID);
+        HirFileId(id | Self::SYNTHETIC_FILE_TAG_MASK)
+    }
+}
+
 impl HirFileId {
-    const MAX_FILE_ID: u32 = u32::MAX ^ Self::MACRO_FILE_TAG_MASK;
+    const MAX_FILE_ID: u32 = Self::SYNTHETIC_FILE_TAG_MASK;
+    const MAX_MACRO_FILE_ID: u32 = u32::MAX ^ Self::MACRO_FILE_TAG_MASK;
     const MACRO_FILE_TAG_MASK: u32 = 1 << 31;
+    const SYNTHETIC_FILE_TAG_MASK: u32 = 1 << 30;
 
     /// For macro-expansion files, returns the file original source file the
     /// expansion originated from.
+    ///
+    /// Panics for synthetic files and expansions of macro calls within them, use
+    /// [`HirFileId::try_original_file`] where these can show up.
     pub fn original_file(self, db: &dyn db::AstDatabase) -> FileId {
+        match self.try_original_file(db) {
+            Some(file_id) => file_id,
+            None => panic!("{self:?} does not originate from a real file"),
+        }
+    }
+
+    /// Like [`HirFileId::original_file`], but returns `None` for synthetic files and expansions of
+    /// macro calls within them, as these do not originate from a real file.
+    pub fn try_original_file(self, db: &dyn db::AstDatabase) -> Option<FileId> {
         let mut file_id = self;
         loop {
             match file_id.repr() {
-                HirFileIdRepr::FileId(id) => break id,
+                HirFileIdRepr::FileId(id) => break Some(id),
+                HirFileIdRepr::SyntheticFile(_) => break None,
                 HirFileIdRepr::MacroFile(MacroFile { macro_call_id }) => {
                     let loc: MacroCallLoc = db.lookup_intern_macro_call(macro_call_id);
                     file_id = match loc.eager {
@@ -205,6 +293,34 @@ pub fn original_file(self, db: &dyn db::AstDatabase) -> FileId {
         }
     }
 
+    /// Returns whether `self` and `other` originate from the same real file, see
+    /// [`HirFileId::try_original_file`].
+    ///
+    /// Synthetic files only share their original file with themselves.
+    pub fn shares_original_file(self, db: &dyn db::AstDatabase, other: HirFileId) -> bool {
+        if self == other {
+            return true;
+        }
+        match (self.try_original_file(db), other.try_original_file(db)) {
+            (Some(this), Some(other)) => this == other,
+            _ => false,
+        }
+    }
+
+    /// Returns the names of the macros whose expansions this file is nested in, from the outermost
+    /// to the innermost one.
+    pub fn macro_stack_trace(self, db: &dyn db::AstDatabase) -> Vec<Name> {
+        let mut names = Vec::new();
+        let mut curr = self;
+        while let Some(macro_file) = curr.macro_file() {
+            let loc: MacroCallLoc = db.lookup_intern_macro_call(macro_file.macro_call_id);
+            names.push(loc.def.name(db));
+            curr = loc.kind.file_id();
+        }
+        names.reverse();
+        names
+    }
+
     pub fn expansion_level(self, db: &dyn db::AstDatabase) -> u32 {
         let mut level = 0;
         let mut curr = self;
@@ -217,6 +333,65 @@ pub fn expansion_level(self, db: &dyn db::AstDatabase) -> u32 {
         level
     }
 
+    /// If the tokens of this file are a verbatim copy of a contiguous piece of the original file, as
+    /// is the case for macros that pass their input through, returns the original file and the
+    /// offset to add to the start of this file to get the start of that piece.
+    ///
+    /// Expansions don't keep the whitespace and comments of their input, so only the tokens are
+    /// compared, and positions after the first token are additionally shifted by the trivia the
+    /// original has in between. Returns `None` whenever the tokens are not such a copy, use the
+    /// token mapping of [`ExpansionInfo`] for those expansions.
+    pub fn to_original_with_offset(self, db: &dyn db::AstDatabase) -> Option<(FileId, i64)> {
+        let to_i64 = |offset: TextSize| i64::from(u32::from(offset));
+        let significant_tokens = |node: &SyntaxNode, range: TextRange| {
+            node.descendants_with_tokens()
+                .filter_map(|it| it.into_token())
+                .filter(move |it| !it.kind().is_trivia() && range.contains_range(it.text_range()))
+        };
+        let mut file_id = self;
+        let mut delta = 0;
+        while file_id.is_macro() {
+            let info = file_id.expansion_info(db)?;
+            let expanded = &info.expanded.value;
+
+            let mut parent_file = None;
+            let mut copied = Vec::new();
+            for token in significant_tokens(expanded, expanded.text_range()) {
+                let (up, origin) = info.map_token_up(db, InFile::new(file_id, &token))?;
+                if origin != Origin::Call || up.value.text() != token.text() {
+                    return None;
+                }
+                match parent_file {
+                    None => parent_file = Some(up.file_id),
+                    Some(it) if it == up.file_id => (),
+                    Some(_) => return None,
+                }
+                copied.push((token, up.value));
+            }
+            let parent_file = parent_file?;
+            let (first, first_up) = copied.first()?;
+            let (_, last_up) = copied.last()?;
+
+            // The tokens have to be copied in order and without leaving any out.
+            let (start, end) = (first_up.text_range().start(), last_up.text_range().end());
+            if start > end {
+                return None;
+            }
+            let range = TextRange::new(start, end);
+            let parent_root = db.parse_or_expand(parent_file)?;
+            let is_copy = significant_tokens(&parent_root, range)
+                .map(|it| it.text_range())
+                .eq(copied.iter().map(|(_, up)| up.text_range()));
+            if !is_copy {
+                return None;
+            }
+
+            delta += to_i64(first_up.text_range().start()) - to_i64(first.text_range().start());
+            file_id = parent_file;
+        }
+        Some((file_id.try_original_file(db)?, delta))
+    }
+
     /// If this is a macro call, returns the syntax node of the call.
     pub fn call_node(self, db: &dyn db::AstDatabase) -> Option<InFile<SyntaxNode>> {
         let macro_file = self.macro_file()?;
@@ -225,12 +400,15 @@ pub fn call_node(self, db: &dyn db::AstDatabase) -> Option<InFile<SyntaxNode>> {
     }
 
     /// If this is a macro call, returns the syntax node of the very first macro call this file resides in.
+    ///
+    /// Returns `None` if that call is in a synthetic file.
     pub fn original_call_node(self, db: &dyn db::AstDatabase) -> Option<(FileId, SyntaxNode)> {
         let mut call =
             db.lookup_intern_macro_call(self.macro_file()?.macro_call_id).kind.to_node(db);
         loop {
             match call.file_id.repr() {
                 HirFileIdRepr::FileId(file_id) => break Some((file_id, call.value)),
+                HirFileIdRepr::SyntheticFile(_) => break None,
                 HirFileIdRepr::MacroFile(MacroFile { macro_call_id }) => {
                     call = db.lookup_intern_macro_call(macro_call_id).kind.to_node(db);
                 }
@@ -246,6 +424,7 @@ pub fn expansion_info(self, db: &dyn db::AstDatabase) -> Option<ExpansionInfo> {
         let arg_tt = loc.kind.arg(db)?;
 
         let macro_def = db.macro_def(loc.def).ok()?;
+        db::record_expansion_provenance(db, macro_file);
         let (parse, exp_map) = db.parse_macro_expansion(macro_file).value?;
         let macro_arg = db.macro_arg(macro_file.macro_call_id)?;
 
@@ -343,6 +522,14 @@ pub fn is_macro(self) -> bool {
         self.0 & Self::MACRO_FILE_TAG_MASK != 0
     }
 
+    #[inline]
+    pub fn synthetic_file(self) -> Option<SyntheticFileId> {
+        match self.repr() {
+            HirFileIdRepr::SyntheticFile(it) => Some(it),
+            _ => None,
+        }
+    }
+
     #[inline]
     pub fn macro_file(self) -> Option<MacroFile> {
         match self.0 & Self::MACRO_FILE_TAG_MASK {
@@ -353,12 +540,30 @@ pub fn macro_file(self) -> Option<MacroFile> {
         }
     }
 
+    /// Packs this id into a `u64`, for use as a cheap map key.
+    ///
+    /// Real files occupy the low 32 bits, while macro files additionally get the 33rd bit set,
+    /// and synthetic files the 34th one, so keys of different kinds of files never collide.
+    #[inline]
+    pub fn key(self) -> u64 {
+        match self.repr() {
+            HirFileIdRepr::FileId(FileId(id)) => id as u64,
+            HirFileIdRepr::MacroFile(MacroFile { macro_call_id: MacroCallId(id) }) => {
+                1 << 32 | id.as_u32() as u64
+            }
+            HirFileIdRepr::SyntheticFile(SyntheticFileId(id)) => 1 << 33 | id as u64,
+        }
+    }
+
     fn repr(self) -> HirFileIdRepr {
-        match self.0 & Self::MACRO_FILE_TAG_MASK {
-            0 => HirFileIdRepr::FileId(FileId(self.0)),
-            _ => HirFileIdRepr::MacroFile(MacroFile {
+        if self.0 & Self::MACRO_FILE_TAG_MASK != 0 {
+            HirFileIdRepr::MacroFile(MacroFile {
                 macro_call_id: MacroCallId(InternId::from(self.0 ^ Self::MACRO_FILE_TAG_MASK)),
-            }),
+            })
+        } else if self.0 & Self::SYNTHETIC_FILE_TAG_MASK != 0 {
+            HirFileIdRepr::SyntheticFile(SyntheticFileId(self.0 ^ Self::SYNTHETIC_FILE_TAG_MASK))
+        } else {
+            HirFileIdRepr::FileId(FileId(self.0))
         }
     }
 }
@@ -385,6 +590,17 @@ pub fn ast_id(&self) -> Either<AstId<ast::Macro>, AstId<ast::Fn>> {
         Either::Left(id)
     }
 
+    /// Returns the name of the item defining this macro.
+    ///
+    /// For proc-macros, this is the name of the function implementing the macro.
+    pub fn name(&self, db: &dyn db::AstDatabase) -> Name {
+        let name = match self.ast_id() {
+            Either::Left(id) => id.to_node(db).name(),
+            Either::Right(id) => id.to_node(db).name(),
+        };
+        name.map_or_else(Name::missing, |it| it.as_name())
+    }
+
     pub fn is_proc_macro(&self) -> bool {
         matches!(self.kind, MacroDefKind::ProcMacro(..))
     }
@@ -395,6 +611,23 @@ pub fn is_attribute(&self) -> bool {
             MacroDefKind::BuiltInAttr(..) | MacroDefKind::ProcMacro(_, ProcMacroKind::Attr, _)
         )
     }
+
+    /// Returns whether this is one of the builtin inline assembly macros, see
+    /// [`BuiltinFnLikeExpander::is_asm`].
+    pub fn is_asm(&self) -> bool {
+        matches!(self.kind, MacroDefKind::BuiltIn(expander, _) if expander.is_asm())
+    }
+
+    /// Returns whether this is a user-written `macro` 2.0 definition, as opposed to a
+    /// `macro_rules!` one.
+    ///
+    /// These follow the stricter definition site hygiene, which is not modeled yet.
+    pub fn is_macro2(&self, db: &dyn db::AstDatabase) -> bool {
+        match self.kind {
+            MacroDefKind::Declarative(id) => matches!(id.to_node(db), ast::Macro::MacroDef(_)),
+            _ => false,
+        }
+    }
 }
 
 // FIXME: attribute indices do not account for `cfg_attr`, which means that we'll strip the whole
@@ -402,7 +635,7 @@ pub fn is_attribute(&self) -> bool {
 
 impl MacroCallKind {
     /// Returns the file containing the macro invocation.
-    fn file_id(&self) -> HirFileId {
+    pub fn file_id(&self) -> HirFileId {
         match *self {
             MacroCallKind::FnLike { ast_id: InFile { file_id, .. }, .. }
             | MacroCallKind::Derive { ast_id: InFile { file_id, .. }, .. }
@@ -448,6 +681,8 @@ pub fn to_node(&self, db: &dyn db::AstDatabase) -> InFile<SyntaxNode> {
     /// Returns the original file range that best describes the location of this macro call.
     ///
     /// Unlike `MacroCallKind::original_call_range`, this also spans the item of attributes and derives.
+    ///
+    /// Panics if the outermost call is in a synthetic file.
     pub fn original_call_range_with_body(self, db: &dyn db::AstDatabase) -> FileRange {
         let mut kind = self;
         let file_id = loop {
@@ -456,6 +691,9 @@ pub fn original_call_range_with_body(self, db: &dyn db::AstDatabase) -> FileRang
                     kind = db.lookup_intern_macro_call(file.macro_call_id).kind;
                 }
                 HirFileIdRepr::FileId(file_id) => break file_id,
+                HirFileIdRepr::SyntheticFile(file_id) => {
+                    panic!("{file_id:?} does not originate from a real file")
+                }
             }
         };
 
@@ -473,6 +711,8 @@ pub fn original_call_range_with_body(self, db: &dyn db::AstDatabase) -> FileRang
     /// Here we try to roughly match what rustc does to improve diagnostics: fn-like macros
     /// get the whole `ast::MacroCall`, attribute macros get the attribute's range, and derives
     /// get only the specific derive that is being referred to.
+    ///
+    /// Panics if the outermost call is in a synthetic file.
     pub fn original_call_range(self, db: &dyn db::AstDatabase) -> FileRange {
         let mut kind = self;
         let file_id = loop {
@@ -481,6 +721,9 @@ pub fn original_call_range(self, db: &dyn db::AstDatabase) -> FileRange {
                     kind = db.lookup_intern_macro_call(file.macro_call_id).kind;
                 }
                 HirFileIdRepr::FileId(file_id) => break file_id,
+                HirFileIdRepr::SyntheticFile(file_id) => {
+                    panic!("{file_id:?} does not originate from a real file")
+                }
             }
         };
 
@@ -623,34 +866,96 @@ pub fn map_token_down(
             None
         };
 
-        let token_id = match token_id_in_attr_input {
-            Some(token_id) => token_id,
+        let token_ids: SmallVec<[tt::TokenId; 1]> = match token_id_in_attr_input {
+            Some(token_id) => smallvec![token_id],
             // the token is not inside an attribute's input so do the lookup in the macro_arg as usual
             None => {
                 let relative_range =
                     token.value.text_range().checked_sub(self.arg.value.text_range().start())?;
-                let token_id = self.macro_arg.1.token_by_range(relative_range)?;
+                let token_ids =
+                    token_ids_by_range(&self.macro_arg.1, relative_range, token.value.kind());
+                if token_ids.is_empty() {
+                    return None;
+                }
                 // conditionally shift the id by a declaratives macro definition
-                self.macro_def.map_id_down(token_id)
+                token_ids.into_iter().map(|token_id| self.macro_def.map_id_down(token_id)).collect()
             }
         };
 
-        let tokens = self
-            .exp_map
-            .ranges_by_token(token_id, token.value.kind())
-            .flat_map(move |range| self.expanded.value.covering_element(range).into_token());
+        let kind = token.value.kind();
+        let tokens = token_ids
+            .into_iter()
+            .flat_map(move |token_id| self.exp_map.ranges_by_token(token_id, kind))
+            .filter_map(move |range| range_in_tree(self.expanded.value.text_range(), range))
+            .flat_map(move |range| self.expanded.value.covering_element(range).into_token())
+            // the characters of a composite punct may all land in the same token again
+            .dedup();
 
         Some(tokens.map(move |token| self.expanded.with_value(token)))
     }
 
+    /// Map a node of the macro input down into `expanded_root`, the root of the expansion.
+    ///
+    /// The first and last tokens of the node are mapped down, and the tightest node enclosing the
+    /// range between them is returned. This is the node itself if it was passed through as is, but
+    /// may also be a node the macro wrapped the input in. Only nodes in the call's token tree can be
+    /// mapped, not ones in attribute inputs.
+    pub fn map_node_down(
+        &self,
+        src: SyntaxNodePtr,
+        expanded_root: &SyntaxNode,
+    ) -> Option<SyntaxNode> {
+        let range = src.text_range();
+        if !self.arg.value.text_range().contains_range(range) {
+            return None;
+        }
+        let mut tokens = self
+            .arg
+            .value
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| !it.kind().is_trivia() && range.contains_range(it.text_range()));
+        let first = tokens.next()?;
+        let last = tokens.last().unwrap_or_else(|| first.clone());
+
+        let start = self.ranges_down(&first).map(|it| it.start()).min()?;
+        // The last token may have been mapped several times, pick the first copy after `start`.
+        let end = self.ranges_down(&last).map(|it| it.end()).filter(|&it| it > start).min()?;
+        let range = range_in_tree(expanded_root.text_range(), TextRange::new(start, end))?;
+        match expanded_root.covering_element(range) {
+            NodeOrToken::Node(node) => Some(node),
+            NodeOrToken::Token(token) => token.parent(),
+        }
+    }
+
+    /// Returns the ranges in the expansion a token of the call's token tree was mapped to.
+    fn ranges_down<'a>(&'a self, token: &SyntaxToken) -> impl Iterator<Item = TextRange> + 'a {
+        let kind = token.kind();
+        let token_ids = token
+            .text_range()
+            .checked_sub(self.arg.value.text_range().start())
+            .map(|range| token_ids_by_range(&self.macro_arg.1, range, kind))
+            .unwrap_or_default();
+        token_ids
+            .into_iter()
+            .map(|token_id| self.macro_def.map_id_down(token_id))
+            .flat_map(move |token_id| self.exp_map.ranges_by_token(token_id, kind))
+    }
+
     /// Map a token up out of the expansion it resides in into the arguments of the macro call of the expansion.
+    ///
+    /// Tokens that were written in a `macro_rules!` definition instead, like the separators of
+    /// repetitions, map into the definition with [`Origin::Def`].
     pub fn map_token_up(
         &self,
         db: &dyn db::AstDatabase,
         token: InFile<&SyntaxToken>,
     ) -> Option<(InFile<SyntaxToken>, Origin)> {
         // Fetch the id through its text range,
-        let token_id = self.exp_map.token_by_range(token.value.text_range())?;
+        let token_id =
+            token_ids_by_range(&self.exp_map, token.value.text_range(), token.value.kind())
+                .into_iter()
+                .next()?;
         // conditionally unshifting the id to accommodate for macro-rules def site
         let (mut token_id, origin) = self.macro_def.map_id_up(token_id);
 
@@ -685,15 +990,99 @@ pub fn map_token_up(
         };
 
         let range = token_map.first_range_by_token(token_id, token.value.kind())?;
-        let token =
-            tt.value.covering_element(range + tt.value.text_range().start()).into_token()?;
+        let range = range_in_tree(tt.value.text_range(), range + tt.value.text_range().start())?;
+        let token = tt.value.covering_element(range).into_token()?;
         Some((tt.with_value(token), origin))
     }
+
+    /// Returns the ranges of the first `expanded_len` bytes of the expansion that are not covered
+    /// by any token of the macro input, that is, code that was introduced by the macro itself.
+    ///
+    /// Adjacent ranges are merged, and the result is sorted.
+    pub fn unmapped_regions(&self, expanded_len: TextSize) -> Vec<TextRange> {
+        let is_mapped = |token: &SyntaxToken| {
+            let ids = token_ids_by_range(&self.exp_map, token.text_range(), token.kind());
+            !ids.is_empty()
+                && ids.into_iter().all(|id| {
+                    if id == tt::TokenId::unspecified() {
+                        return false;
+                    }
+                    match self.macro_def.map_id_up(id) {
+                        (id, mbe::Origin::Call) => {
+                            self.macro_arg_shift.unshift(id).is_some()
+                                || self.macro_arg.1.first_range_by_token(id, token.kind()).is_some()
+                        }
+                        (_, mbe::Origin::Def) => false,
+                    }
+                })
+        };
+
+        let mut res: Vec<TextRange> = Vec::new();
+        let tokens = self.expanded.value.descendants_with_tokens().filter_map(|it| it.into_token());
+        for token in tokens {
+            let range = token.text_range();
+            if range.start() >= expanded_len {
+                break;
+            }
+            if is_mapped(&token) {
+                continue;
+            }
+            let range = TextRange::new(range.start(), range.end().min(expanded_len));
+            match res.last_mut() {
+                Some(last) if last.end() == range.start() => *last = last.cover(range),
+                _ => res.push(range),
+            }
+        }
+        res
+    }
+}
+
+/// Checks that a range taken from a token map lies within the tree it is supposed to point into.
+///
+/// A bug in building a token map shouldn't make us panic somewhere deep down in rowan, so such
+/// ranges are just dropped.
+fn range_in_tree(tree: TextRange, range: TextRange) -> Option<TextRange> {
+    tree.contains_range(range).then_some(range)
+}
+
+/// Looks up the ids of the tokens covering `range`.
+///
+/// Composite puncts like `<<` are lowered into one joint `Punct` per character, so the token map
+/// only knows about the single characters. Whenever the parser glues such puncts back together
+/// (or the source already had them glued), the range of the resulting token is split evenly into
+/// its characters, and the ids of all of them are returned.
+fn token_ids_by_range(
+    token_map: &mbe::TokenMap,
+    range: TextRange,
+    kind: SyntaxKind,
+) -> SmallVec<[tt::TokenId; 1]> {
+    if let Some(token_id) = token_map.token_by_range(range) {
+        return smallvec![token_id];
+    }
+    if !kind.is_punct() {
+        return SmallVec::new();
+    }
+    let char_len = TextSize::of('.');
+    let mut res = SmallVec::new();
+    let mut start = range.start();
+    while start < range.end() {
+        match token_map.token_by_range(TextRange::at(start, char_len)) {
+            Some(token_id) => res.push(token_id),
+            None => return SmallVec::new(),
+        }
+        start += char_len;
+    }
+    res
 }
 
 /// `AstId` points to an AST node in any file.
 ///
 /// It is stable across reparses, and can be used as salsa key/value.
+///
+/// With the `serde` feature, it can be serialized as the `HirFileId` and the raw index of the
+/// `FileAstId`. Note that neither of them carries any information about the source they were
+/// created from, so a deserialized id is only meaningful for the very same source revision (and
+/// interning state, for macro files).
 pub type AstId<N> = InFile<FileAstId<N>>;
 
 impl<N: AstNode> AstId<N> {
@@ -711,6 +1100,7 @@ pub fn to_node(&self, db: &dyn db::AstDatabase) -> N {
 /// * `InFile<ast::FnDef>` -- ast node in a file
 /// * `InFile<TextSize>` -- offset in a file
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InFile<T> {
     pub file_id: HirFileId,
     pub value: T,
@@ -733,6 +1123,11 @@ pub fn as_ref(&self) -> InFile<&T> {
         self.with_value(&self.value)
     }
 
+    /// Destructures this into the file id and the value, the inverse of [`InFile::new`].
+    pub fn split(self) -> (HirFileId, T) {
+        (self.file_id, self.value)
+    }
+
     pub fn file_syntax(&self, db: &dyn db::AstDatabase) -> SyntaxNode {
         db.parse_or_expand(self.file_id).expect("source created from invalid file")
     }
@@ -751,6 +1146,16 @@ pub fn transpose(self) -> Option<InFile<T>> {
     }
 }
 
+impl<T> InFile<InFile<T>> {
+    /// Collapses the nesting, keeping the inner `file_id`.
+    ///
+    /// The inner value was produced with knowledge of the file it actually lives in, while the
+    /// outer `file_id` usually just records where the lookup started, so the inner one wins.
+    pub fn flatten(self) -> InFile<T> {
+        self.value
+    }
+}
+
 impl<'a> InFile<&'a SyntaxNode> {
     pub fn ancestors_with_macros(
         self,
@@ -787,9 +1192,14 @@ pub fn ancestors_with_macros_skip_attr_item(
     ///
     /// For attributes and derives, this will point back to the attribute only.
     /// For the entire item `InFile::use original_file_range_full`.
+    ///
+    /// Panics for nodes of synthetic files, and of expansions of macro calls within them.
     pub fn original_file_range(self, db: &dyn db::AstDatabase) -> FileRange {
         match self.file_id.repr() {
             HirFileIdRepr::FileId(file_id) => FileRange { file_id, range: self.value.text_range() },
+            HirFileIdRepr::SyntheticFile(file_id) => {
+                panic!("{file_id:?} does not originate from a real file")
+            }
             HirFileIdRepr::MacroFile(mac_file) => {
                 if let Some(res) = self.original_file_range_opt(db) {
                     return res;
@@ -805,7 +1215,7 @@ pub fn original_file_range(self, db: &dyn db::AstDatabase) -> FileRange {
     pub fn original_file_range_opt(self, db: &dyn db::AstDatabase) -> Option<FileRange> {
         match ascend_node_border_tokens(db, self) {
             Some(InFile { file_id, value: (first, last) }) => {
-                let original_file = file_id.original_file(db);
+                let original_file = file_id.try_original_file(db)?;
                 let range = first.text_range().cover(last.text_range());
                 if file_id != original_file.into() {
                     tracing::error!("Failed mapping up more for {:?}", range);
@@ -814,7 +1224,7 @@ pub fn original_file_range_opt(self, db: &dyn db::AstDatabase) -> Option<FileRan
                 Some(FileRange { file_id: original_file, range })
             }
             _ if !self.file_id.is_macro() => Some(FileRange {
-                file_id: self.file_id.original_file(db),
+                file_id: self.file_id.try_original_file(db)?,
                 range: self.value.text_range(),
             }),
             _ => None,
@@ -854,9 +1264,14 @@ pub fn upmap(self, db: &dyn db::AstDatabase) -> Option<InFile<SyntaxToken>> {
     }
 
     /// Falls back to the macro call range if the node cannot be mapped up fully.
+    ///
+    /// Panics for tokens of synthetic files, and of expansions of macro calls within them.
     pub fn original_file_range(self, db: &dyn db::AstDatabase) -> FileRange {
         match self.file_id.repr() {
             HirFileIdRepr::FileId(file_id) => FileRange { file_id, range: self.value.text_range() },
+            HirFileIdRepr::SyntheticFile(file_id) => {
+                panic!("{file_id:?} does not originate from a real file")
+            }
             HirFileIdRepr::MacroFile(mac_file) => {
                 if let Some(res) = self.original_file_range_opt(db) {
                     return res;
@@ -874,10 +1289,11 @@ pub fn original_file_range_opt(self, db: &dyn db::AstDatabase) -> Option<FileRan
             HirFileIdRepr::FileId(file_id) => {
                 Some(FileRange { file_id, range: self.value.text_range() })
             }
+            HirFileIdRepr::SyntheticFile(_) => None,
             HirFileIdRepr::MacroFile(_) => {
                 let expansion = self.file_id.expansion_info(db)?;
                 let InFile { file_id, value } = ascend_call_token(db, &expansion, self)?;
-                let original_file = file_id.original_file(db);
+                let original_file = file_id.try_original_file(db)?;
                 if file_id != original_file.into() {
                     return None;
                 }
@@ -963,6 +1379,20 @@ pub fn syntax(&self) -> InFile<&SyntaxNode> {
     }
 }
 
+impl InFile<ast::MacroCall> {
+    /// Lowers the argument delimiter group of this call to a token tree, without expanding (or
+    /// even resolving) the macro.
+    ///
+    /// This is a plain conversion of the syntax and not what the macro sees: unlike the
+    /// `macro_arg` query, it neither censors attributes nor applies syntax fixups, and its token
+    /// ids are unrelated to the ones of the call's expansion. Once the call is resolved to a
+    /// `MacroCallId`, use `db.macro_arg` instead.
+    pub fn arg_tt(&self) -> Option<tt::Subtree> {
+        let arg = self.value.token_tree()?;
+        Some(mbe::syntax_node_to_token_tree(arg.syntax()).0)
+    }
+}
+
 /// In Rust, macros expand token trees to token trees. When we want to turn a
 /// token tree into an AST node, we need to figure out what kind of AST node we
 /// want: something like `foo` can be a type, an expression, or a pattern.
@@ -978,7 +1408,11 @@ pub fn syntax(&self) -> InFile<&SyntaxNode> {
 ///
 /// `ExpandTo` captures this bit of information about a particular macro call
 /// site.
+///
+/// Outside of this crate, prefer the `is_*` accessors over matching on the variants, as more may
+/// be added.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum ExpandTo {
     Statements,
     Items,
@@ -1025,9 +1459,408 @@ pub fn from_call_site(call: &ast::MacroCall) -> ExpandTo {
             }
         }
     }
+
+    pub fn is_statements(self) -> bool {
+        self == ExpandTo::Statements
+    }
+
+    pub fn is_items(self) -> bool {
+        self == ExpandTo::Items
+    }
+
+    pub fn is_pattern(self) -> bool {
+        self == ExpandTo::Pattern
+    }
+
+    pub fn is_type(self) -> bool {
+        self == ExpandTo::Type
+    }
+
+    pub fn is_expr(self) -> bool {
+        self == ExpandTo::Expr
+    }
 }
 
 #[derive(Debug)]
 pub struct UnresolvedMacro {
     pub path: ModPath,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use base_db::{
+        fixture::WithFixture,
+        salsa::{InternId, InternKey},
+        FileId, SourceDatabaseExt,
+    };
+
+    use mbe::Origin;
+    use syntax::{
+        ast, AstNode, SourceFile, SyntaxElement, SyntaxNode, SyntaxNodePtr, SyntaxToken, TextSize,
+    };
+
+    use crate::{
+        builtin_fn_macro::BuiltinFnLikeExpander,
+        db::{AstDatabase, TokenExpander},
+        fixup,
+        test_db::TestDB,
+        ExpansionInfo, HirFileId, InFile, MacroCallId, MacroFile, SyntheticFileId,
+    };
+
+    fn find_token(node: &SyntaxNode, text: &str) -> SyntaxToken {
+        node.descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .find(|it| it.text() == text)
+            .unwrap()
+    }
+
+    #[test]
+    fn split_round_trips_through_new() {
+        let file_id = HirFileId::from(FileId(3));
+        let (split_file_id, value) = InFile::new(file_id, "value").split();
+        assert_eq!(split_file_id, file_id);
+        assert_eq!(value, "value");
+        assert_eq!(InFile::new(split_file_id, value), InFile::new(file_id, "value"));
+    }
+
+    #[test]
+    fn flatten_keeps_inner_file_id() {
+        let outer = HirFileId::from(FileId(0));
+        let inner = HirFileId::from(FileId(1));
+        let nested = InFile::new(outer, InFile::new(inner, 92));
+        assert_eq!(nested.flatten(), InFile::new(inner, 92));
+    }
+
+    #[test]
+    fn file_id_keys_are_distinct() {
+        let macro_file = |id: u32| {
+            HirFileId::from(MacroFile {
+                macro_call_id: MacroCallId::from_intern_id(InternId::from(id)),
+            })
+        };
+        let ids = [
+            HirFileId::from(FileId(0)),
+            HirFileId::from(FileId(1)),
+            HirFileId::from(FileId(92)),
+            macro_file(0),
+            macro_file(1),
+            macro_file(92),
+            HirFileId::from(SyntheticFileId(0)),
+            HirFileId::from(SyntheticFileId(1)),
+            HirFileId::from(SyntheticFileId(92)),
+        ];
+        for (i, a) in ids.iter().enumerate() {
+            for (j, b) in ids.iter().enumerate() {
+                assert_eq!(i == j, a.key() == b.key(), "{a:?} vs {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn macro_call_arg_tt() {
+        let file = SourceFile::parse("foo!(a, b);").tree();
+        let call = file.syntax().descendants().find_map(ast::MacroCall::cast).unwrap();
+        let tt = InFile::new(FileId(0).into(), call).arg_tt().unwrap();
+
+        assert_eq!(tt.delimiter_kind(), Some(tt::DelimiterKind::Parenthesis));
+        let leaves = tt
+            .token_trees
+            .iter()
+            .map(|it| match it {
+                tt::TokenTree::Leaf(leaf) => leaf.to_string(),
+                tt::TokenTree::Subtree(_) => panic!("unexpected subtree"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(leaves, ["a", ",", "b"]);
+    }
+
+    #[test]
+    fn out_of_bounds_token_map_ranges_are_dropped() {
+        let file = SourceFile::parse("foo!(a + b);").tree();
+        let arg = file.syntax().descendants().find_map(ast::TokenTree::cast).unwrap();
+        let (subtree, arg_map) = mbe::syntax_node_to_token_tree(arg.syntax());
+        let (expanded, exp_map) =
+            mbe::token_tree_to_syntax_node(&subtree, mbe::TopEntryPoint::Expr);
+        let expanded = expanded.syntax_node();
+        // The same tokens, but taken from a longer text, so their ranges run past the expansion.
+        let (_, bad_exp_map) = mbe::parse_to_token_tree("(aaaaaaaaaa + bbbbbbbbbb)").unwrap();
+
+        let map_down = |exp_map| {
+            let info = ExpansionInfo {
+                expanded: InFile::new(
+                    MacroFile { macro_call_id: MacroCallId::from_intern_id(InternId::from(0u32)) }
+                        .into(),
+                    expanded.clone(),
+                ),
+                arg: InFile::new(FileId(0).into(), arg.syntax().clone()),
+                attr_input_or_mac_def: None,
+                macro_def: Arc::new(TokenExpander::Builtin(BuiltinFnLikeExpander::Stringify)),
+                macro_arg: Arc::new((
+                    subtree.clone(),
+                    arg_map.clone(),
+                    fixup::fixup_syntax(arg.syntax()).undo_info,
+                )),
+                macro_arg_shift: mbe::Shift::new(&subtree),
+                exp_map: Arc::new(exp_map),
+            };
+            info.map_node_down(SyntaxNodePtr::new(arg.syntax()), &expanded).map(|it| it.to_string())
+        };
+
+        assert_eq!(map_down(exp_map).as_deref(), Some("(a+b)"));
+        assert_eq!(map_down(bad_exp_map), None);
+    }
+
+    #[test]
+    fn map_joint_puncts_through_expansion() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($e:expr) => { const C: u32 = $e; };
+}
+m!(1 << 2);
+"#,
+        );
+        let (call, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        let file_id = macro_call_id.as_file();
+        let expansion = file_id.expansion_info(&db).unwrap();
+        let expanded = db.parse_or_expand(file_id).unwrap();
+
+        // The token tree of the call keeps the two `<` apart, the expansion parses them into one
+        // `<<`.
+        let src_lts: Vec<_> = call
+            .value
+            .syntax()
+            .descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .filter(|it| it.text() == "<")
+            .collect();
+        assert_eq!(src_lts.len(), 2);
+        let shl = find_token(&expanded, "<<");
+        for src_lt in &src_lts {
+            let mapped: Vec<_> = expansion
+                .map_token_down(&db, None, InFile::new(call.file_id, src_lt))
+                .unwrap()
+                .collect();
+            assert_eq!(mapped.len(), 1);
+            assert_eq!(mapped[0].value, shl);
+        }
+
+        let (mapped, _) = expansion.map_token_up(&db, InFile::new(file_id, &shl)).unwrap();
+        assert_eq!(mapped, InFile::new(call.file_id, src_lts[0].clone()));
+    }
+
+    #[test]
+    fn affine_expansion_offset() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! id {
+    ($($tt:tt)*) => { $($tt)* };
+}
+id! {
+    struct S {
+        // The field.
+        field: u32,
+    }
+}
+"#,
+        );
+        let (_, id) = db.macro_calls(file_id).pop().unwrap();
+        let (original, delta) = id.as_file().to_original_with_offset(&db).unwrap();
+        assert_eq!(original, file_id);
+
+        let text = db.file_text(file_id);
+        assert_eq!(delta, text.find("struct S").unwrap() as i64);
+    }
+
+    #[test]
+    fn non_affine_expansion_offset() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! def_site {
+    () => { struct S; };
+}
+macro_rules! swap {
+    ($a:tt $b:tt) => { $b$a };
+}
+def_site!();
+swap!(S impl);
+"#,
+        );
+        let ids: Vec<_> = db.macro_calls(file_id).into_iter().map(|(_, id)| id).collect();
+        assert_eq!(ids.len(), 2);
+        for id in ids {
+            assert_eq!(id.as_file().to_original_with_offset(&db), None);
+        }
+    }
+
+    #[test]
+    fn repeated_metavariable_uses_map_to_the_same_source() {
+        fn check(db: &TestDB, file_id: FileId, arg: &str) {
+            let (call, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+            let file_id = macro_call_id.as_file();
+            let expansion = file_id.expansion_info(db).unwrap();
+            let expanded = db.parse_or_expand(file_id).unwrap();
+
+            let src = find_token(call.value.syntax(), arg);
+            let uses: Vec<_> = expanded
+                .descendants_with_tokens()
+                .filter_map(SyntaxElement::into_token)
+                .filter(|it| it.text() == arg)
+                .collect();
+            assert_eq!(uses.len(), 3);
+            for token in &uses {
+                let (mapped, _) = expansion.map_token_up(db, InFile::new(file_id, token)).unwrap();
+                assert_eq!(mapped, InFile::new(call.file_id, src.clone()));
+            }
+
+            let mut mapped: Vec<_> = expansion
+                .map_token_down(db, None, InFile::new(call.file_id, &src))
+                .unwrap()
+                .map(|it| it.value)
+                .collect();
+            mapped.sort_by_key(|it| it.text_range().start());
+            assert_eq!(mapped, uses);
+        }
+
+        let (mut db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($x:ident) => { struct $x; impl $x { fn $x() {} } };
+}
+m!(foo);
+"#,
+        );
+        check(&db, file_id, "foo");
+
+        let text = db.file_text(file_id).replace("m!(foo)", "m!(renamed)");
+        db.set_file_text(file_id, Arc::new(text));
+        check(&db, file_id, "renamed");
+    }
+
+    #[test]
+    fn repetition_separators_map_to_definition() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($($x:ident)*) => { fn f() { ($($x),*); } };
+}
+m!(a b c);
+"#,
+        );
+        let (_, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        let macro_file = macro_call_id.as_file();
+        let expansion = macro_file.expansion_info(&db).unwrap();
+        let expanded = db.parse_or_expand(macro_file).unwrap();
+
+        let separators: Vec<_> = expanded
+            .descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .filter(|it| it.text() == ",")
+            .collect();
+        assert_eq!(separators.len(), 2);
+
+        let text = db.file_text(file_id);
+        let def_separator = text.find("),*").unwrap() + 1;
+        for separator in &separators {
+            let (mapped, origin) =
+                expansion.map_token_up(&db, InFile::new(macro_file, separator)).unwrap();
+            assert_eq!(origin, Origin::Def);
+            assert_eq!(mapped.file_id, file_id.into());
+            assert_eq!(usize::from(mapped.value.text_range().start()), def_separator);
+        }
+    }
+
+    #[test]
+    fn macro_2_0_definitions_are_told_apart() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! rules {
+    () => {};
+}
+macro two {
+    () => {},
+}
+rules!();
+two!();
+"#,
+        );
+        let is_macro2: Vec<_> = db
+            .macro_calls(file_id)
+            .into_iter()
+            .map(|(_, id)| db.lookup_intern_macro_call(id).def.is_macro2(&db))
+            .collect();
+        assert_eq!(is_macro2, [false, true]);
+    }
+
+    #[test]
+    fn unmapped_regions_cover_macro_introduced_code() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    ($i:ident) => { fn $i() {} };
+}
+m!(foo);
+"#,
+        );
+        let (_, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        let file_id = macro_call_id.as_file();
+        let expansion = file_id.expansion_info(&db).unwrap();
+        let expanded = db.parse_or_expand(file_id).unwrap();
+        let text = expanded.to_string();
+        assert_eq!(text, "fnfoo(){}");
+
+        let regions = |len: usize| {
+            let len = TextSize::try_from(len).unwrap();
+            expansion.unmapped_regions(len).into_iter().map(|it| &text[it]).collect::<Vec<_>>()
+        };
+        // Only `foo` comes from the call, the rest is written in the definition.
+        assert_eq!(regions(text.len()), ["fn", "(){}"]);
+        assert_eq!(regions(7), ["fn", "()"]);
+    }
+
+    #[test]
+    fn try_original_file_of_real_and_macro_files() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+macro_rules! m {
+    () => { struct S; };
+}
+m!();
+"#,
+        );
+        let (call, macro_call_id) = db.macro_calls(file_id).pop().unwrap();
+        assert_eq!(call.file_id.original_file(&db), file_id);
+        assert_eq!(macro_call_id.as_file().original_file(&db), file_id);
+        assert_eq!(call.file_id.try_original_file(&db), Some(file_id));
+        assert_eq!(macro_call_id.as_file().try_original_file(&db), Some(file_id));
+    }
+
+    #[test]
+    fn group_files_by_original_file() {
+        let (db, files) = TestDB::with_many_files(
+            r#"
+//- /main.rs
+macro_rules! m {
+    ($i:ident) => { struct $i; };
+}
+mod other;
+m!(A);
+m!(B);
+//- /other.rs
+struct C;
+"#,
+        );
+        let (main_file, other_file) = (HirFileId::from(files[0]), HirFileId::from(files[1]));
+        let calls = db.macro_calls(files[0]);
+        let (from_a, from_b) = (calls[0].1.as_file(), calls[1].1.as_file());
+
+        assert!(from_a.shares_original_file(&db, from_b));
+        assert!(from_a.shares_original_file(&db, main_file));
+        assert!(main_file.shares_original_file(&db, from_b));
+        assert!(!from_a.shares_original_file(&db, other_file));
+        assert!(!other_file.shares_original_file(&db, from_b));
+        assert!(other_file.shares_original_file(&db, other_file));
+    }
+}
@@ -2,7 +2,11 @@
 
 use std::fmt;
 
+use base_db::{impl_intern_key, salsa};
 use syntax::{ast, SmolStr, SyntaxKind};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::db::AstDatabase;
 
 /// `Name` is a wrapper around string, which is used in hir for both references
 /// and declarations. In theory, names should also carry hygiene info, but we are
@@ -14,10 +18,60 @@
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Name(Repr);
 
+/// Interned id of a [`Name`], for use as a compact key in place of the name itself.
+///
+/// See [`Name::as_id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NameId(salsa::InternId);
+impl_intern_key!(NameId);
+
 /// Wrapper of `Name` to print the name without "r#" even when it is a raw identifier.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct UnescapedName<'a>(&'a Name);
 
+/// Kinds of anonymous constructs which get a synthetic [`Name`] for display purposes, see
+/// [`Name::anonymous`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnonKind {
+    Closure,
+    AsyncBlock,
+    ConstBlock,
+}
+
+impl AnonKind {
+    const fn text(self) -> &'static str {
+        match self {
+            AnonKind::Closure => "<closure>",
+            AnonKind::AsyncBlock => "<async block>",
+            AnonKind::ConstBlock => "<const block>",
+        }
+    }
+}
+
+/// Keywords which may be used as path segments, see [`Name::path_keyword`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathKeyword {
+    /// `crate`
+    Crate,
+    /// `super`
+    Super,
+    /// `self`
+    SelfValue,
+    /// `Self`
+    SelfType,
+}
+
+impl PathKeyword {
+    const fn text(self) -> &'static str {
+        match self {
+            PathKeyword::Crate => "crate",
+            PathKeyword::Super => "super",
+            PathKeyword::SelfValue => "self",
+            PathKeyword::SelfType => "Self",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Repr {
     Text(SmolStr),
@@ -33,6 +87,14 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
 fn is_raw_identifier(name: &str) -> bool {
     let is_keyword = SyntaxKind::from_keyword(name).is_some();
     is_keyword && !matches!(name, "self" | "crate" | "super" | "Self")
@@ -79,7 +141,14 @@ pub fn new_tuple_field(idx: usize) -> Name {
         Name(Repr::TupleField(idx))
     }
 
+    #[deprecated(note = "use `Name::from_ast_lifetime` instead")]
     pub fn new_lifetime(lt: &ast::Lifetime) -> Name {
+        Name::from_ast_lifetime(lt)
+    }
+
+    /// Creates the name of a lifetime or label written in the source, keeping its leading
+    /// apostrophe, so `'a` is named `'a` and never collides with an `a` from the value namespace.
+    pub fn from_ast_lifetime(lt: &ast::Lifetime) -> Name {
         Self::new_text(lt.text().into())
     }
 
@@ -89,7 +158,7 @@ const fn new_inline(text: &str) -> Name {
     }
 
     /// Resolve a name from the text of token.
-    fn resolve(raw_text: &str) -> Name {
+    pub(crate) fn resolve(raw_text: &str) -> Name {
         match raw_text.strip_prefix("r#") {
             // When `raw_text` starts with "r#" but the name does not coincide with any
             // keyword, we never need the prefix so we strip it.
@@ -104,6 +173,35 @@ fn resolve(raw_text: &str) -> Name {
         }
     }
 
+    /// Turns arbitrary text, like the result of `stringify!` or `concat!`, into a name that is a
+    /// valid identifier.
+    ///
+    /// Text that already is an identifier is kept as is. Otherwise every character that may not
+    /// appear in an identifier is replaced with `_`, and `_` is prepended if the text does not
+    /// start like an identifier, so `"foo bar"` becomes `foo_bar` and `"1st"` becomes `_1st`. See
+    /// [`Name::is_sanitized`] to tell whether the text had to be rewritten.
+    pub fn sanitize(raw: &str) -> Name {
+        if !Name::is_sanitized(raw) {
+            return Name::resolve(raw);
+        }
+        let mut text = String::with_capacity(raw.len() + 1);
+        if !raw.starts_with(is_ident_start) || raw == "_" {
+            text.push('_');
+        }
+        text.extend(raw.chars().map(|c| if is_ident_continue(c) { c } else { '_' }));
+        Name::new_text(text.into())
+    }
+
+    /// Returns whether [`Name::sanitize`] has to rewrite `raw`, that is whether the name it
+    /// builds is synthetic instead of the identifier `raw`.
+    pub fn is_sanitized(raw: &str) -> bool {
+        let mut chars = raw.chars();
+        let is_ident = raw != "_"
+            && chars.next().map_or(false, is_ident_start)
+            && chars.all(is_ident_continue);
+        !is_ident
+    }
+
     /// A fake name for things missing in the source code.
     ///
     /// For example, `impl Foo for {}` should be treated as a trait impl for a
@@ -117,6 +215,31 @@ pub const fn missing() -> Name {
         Name::new_inline("[missing name]")
     }
 
+    /// A synthetic name for an anonymous construct like a closure, used for labels in the UI.
+    ///
+    /// The text of these names is not a valid identifier, so they never resolve to anything.
+    pub const fn anonymous(kind: AnonKind) -> Name {
+        Name::new_inline(kind.text())
+    }
+
+    /// Returns whether this name was created by [`Name::anonymous`].
+    pub fn is_anonymous(&self) -> bool {
+        match &self.0 {
+            Repr::Text(it) => [AnonKind::Closure, AnonKind::AsyncBlock, AnonKind::ConstBlock]
+                .iter()
+                .any(|kind| it == kind.text()),
+            Repr::TupleField(_) => false,
+        }
+    }
+
+    /// Returns whether this is the name of a lifetime or label, see [`Name::from_ast_lifetime`].
+    pub fn is_lifetime(&self) -> bool {
+        match &self.0 {
+            Repr::Text(it) => it.starts_with('\''),
+            Repr::TupleField(_) => false,
+        }
+    }
+
     /// Returns the tuple index this name represents if it is a tuple field.
     pub fn as_tuple_index(&self) -> Option<usize> {
         match self.0 {
@@ -143,6 +266,99 @@ pub fn to_smol_str(&self) -> SmolStr {
         }
     }
 
+    /// The name of a path segment keyword like `crate` or `super`.
+    pub const fn path_keyword(keyword: PathKeyword) -> Name {
+        Name::new_inline(keyword.text())
+    }
+
+    /// Returns which path segment keyword this name is, if any.
+    pub fn as_path_keyword(&self) -> Option<PathKeyword> {
+        [PathKeyword::Crate, PathKeyword::Super, PathKeyword::SelfValue, PathKeyword::SelfType]
+            .into_iter()
+            .find(|keyword| self.is_text(keyword.text()))
+    }
+
+    pub fn is_crate(&self) -> bool {
+        self.as_path_keyword() == Some(PathKeyword::Crate)
+    }
+
+    pub fn is_super(&self) -> bool {
+        self.as_path_keyword() == Some(PathKeyword::Super)
+    }
+
+    pub fn is_self_value(&self) -> bool {
+        self.as_path_keyword() == Some(PathKeyword::SelfValue)
+    }
+
+    pub fn is_self_type(&self) -> bool {
+        self.as_path_keyword() == Some(PathKeyword::SelfType)
+    }
+
+    /// Returns whether this is the name of the `cfg` attribute.
+    pub fn is_cfg(&self) -> bool {
+        self.is_text(attrs::CFG)
+    }
+
+    /// Returns whether this is the name of the `cfg_attr` attribute.
+    pub fn is_cfg_attr(&self) -> bool {
+        self.is_text(attrs::CFG_ATTR)
+    }
+
+    /// Returns whether this is the name of the `doc` attribute.
+    pub fn is_doc(&self) -> bool {
+        self.is_text(attrs::DOC)
+    }
+
+    fn is_text(&self, text: &str) -> bool {
+        matches!(&self.0, Repr::Text(it) if it == text)
+    }
+
+    /// Returns the label of an associated item with this name qualified by the trait it comes from,
+    /// i.e. `Trait::name`, for disambiguating items of different traits in the UI.
+    ///
+    /// Both names are printed escaped, so that the label is valid to write as a path.
+    pub fn qualified_label(&self, trait_name: &Name) -> String {
+        format!("{trait_name}::{self}")
+    }
+
+    /// Renders this name for display in the UI, cutting it down to at most `max` grapheme clusters
+    /// by replacing its tail with an ellipsis. Only use this for display, never for resolution.
+    ///
+    /// A `max` of zero leaves no room for the ellipsis and yields an empty string.
+    pub fn display_truncated(&self, max: usize) -> String {
+        if max == 0 {
+            return String::new();
+        }
+        let text = self.to_smol_str();
+        let mut starts = text.grapheme_indices(true).map(|(idx, _)| idx);
+        // The ellipsis takes the place of the last grapheme cluster that would still fit.
+        match (starts.nth(max - 1), starts.next()) {
+            (Some(cut), Some(_)) => format!("{}…", &text[..cut]),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Returns whether this name matches `other` ignoring ASCII case and raw identifier prefixes,
+    /// like `Vec` and `vec`. This is meant for fuzzy matching in the UI, resolution must stick to
+    /// `==`.
+    pub fn eq_ignore_case(&self, other: &str) -> bool {
+        let other = other.strip_prefix("r#").unwrap_or(other);
+        match &self.0 {
+            Repr::Text(it) => it.strip_prefix("r#").unwrap_or(it).eq_ignore_ascii_case(other),
+            Repr::TupleField(idx) => other.parse() == Ok(*idx),
+        }
+    }
+
+    /// Interns this name. The returned id is stable within a revision, and can be turned back
+    /// into the name with [`Name::from_id`].
+    pub fn as_id(&self, db: &dyn AstDatabase) -> NameId {
+        db.intern_name(self.clone())
+    }
+
+    pub fn from_id(db: &dyn AstDatabase, id: NameId) -> Name {
+        db.lookup_intern_name(id)
+    }
+
     pub fn unescaped(&self) -> UnescapedName<'_> {
         UnescapedName(self)
     }
@@ -207,6 +423,16 @@ fn as_name(&self) -> Name {
     }
 }
 
+/// Names of well-known attributes, and of meta items used within them like the `hidden` of
+/// `#[doc(hidden)]`.
+pub mod attrs {
+    pub const CFG: &str = "cfg";
+    pub const CFG_ATTR: &str = "cfg_attr";
+    pub const DEPRECATED: &str = "deprecated";
+    pub const DOC: &str = "doc";
+    pub const HIDDEN: &str = "hidden";
+}
+
 pub mod known {
     macro_rules! known_names {
         ($($ident:ident),* $(,)?) => {
@@ -315,7 +541,9 @@ macro_rules! known_names {
         option_env,
         std_panic,
         stringify,
+        todo,
         trace_macros,
+        unimplemented,
         unreachable,
         // Builtin derives
         Copy,
@@ -409,3 +637,187 @@ macro_rules! name {
 }
 
 pub use crate::name;
+
+#[cfg(test)]
+mod tests {
+    use syntax::{ast, AstNode, SourceFile, SyntaxKind};
+
+    use crate::test_db::TestDB;
+
+    use super::{attrs, known, AnonKind, AsName, Name, PathKeyword};
+
+    #[test]
+    fn anonymous_names() {
+        let closure = Name::anonymous(AnonKind::Closure);
+        let async_block = Name::anonymous(AnonKind::AsyncBlock);
+        assert_eq!(closure.to_string(), "<closure>");
+        assert_eq!(async_block.to_string(), "<async block>");
+        assert_ne!(closure, async_block);
+        assert!(closure.is_anonymous());
+        assert!(async_block.is_anonymous());
+        assert!(!Name::missing().is_anonymous());
+        assert!(!Name::new_tuple_field(0).is_anonymous());
+    }
+
+    #[test]
+    fn well_known_attr_names() {
+        let source_file = SourceFile::parse(
+            "#[cfg(test)] #[cfg_attr(test, deprecated)] #[doc(hidden)] #[r#doc] fn f() {}",
+        )
+        .ok()
+        .unwrap();
+        let names: Vec<_> = source_file
+            .syntax()
+            .descendants()
+            .filter_map(ast::Attr::cast)
+            .map(|attr| attr.path().unwrap().as_single_name_ref().unwrap().as_name())
+            .collect();
+
+        assert!(names[0].is_cfg() && !names[0].is_cfg_attr());
+        assert_eq!(names[0].to_smol_str(), attrs::CFG);
+        assert!(names[1].is_cfg_attr() && !names[1].is_cfg());
+        assert_eq!(names[1].to_smol_str(), attrs::CFG_ATTR);
+        assert!(names[2].is_doc());
+        assert!(names[3].is_doc());
+        assert_eq!(names[2], known::doc);
+        assert_eq!(known::cfg.to_smol_str(), attrs::CFG);
+        assert_eq!(known::cfg_attr.to_smol_str(), attrs::CFG_ATTR);
+        assert!(!Name::new_tuple_field(0).is_doc());
+
+        let meta_names: Vec<_> = source_file
+            .syntax()
+            .descendants()
+            .filter_map(ast::TokenTree::cast)
+            .flat_map(|tt| tt.syntax().children_with_tokens().filter_map(|it| it.into_token()))
+            .filter(|it| it.kind() == SyntaxKind::IDENT)
+            .map(|it| it.text().to_string())
+            .collect();
+        assert_eq!(meta_names, ["test", "test", attrs::DEPRECATED, attrs::HIDDEN]);
+    }
+
+    #[test]
+    fn path_keywords() {
+        let keywords =
+            [PathKeyword::Crate, PathKeyword::Super, PathKeyword::SelfValue, PathKeyword::SelfType];
+        for keyword in keywords {
+            let name = Name::path_keyword(keyword);
+            assert_eq!(name.as_path_keyword(), Some(keyword));
+            // A keyword written in source is the very same name.
+            assert_eq!(Name::resolve(keyword.text()), name);
+        }
+        assert_eq!(known::SELF_PARAM, Name::path_keyword(PathKeyword::SelfValue));
+        assert_eq!(known::SELF_TYPE, Name::path_keyword(PathKeyword::SelfType));
+
+        assert!(Name::resolve("crate").is_crate());
+        assert!(Name::resolve("super").is_super());
+        assert!(Name::resolve("self").is_self_value() && !Name::resolve("self").is_self_type());
+        assert!(Name::resolve("Self").is_self_type() && !Name::resolve("Self").is_self_value());
+
+        // Identifiers that merely look alike are never keywords.
+        for text in ["Crate", "$crate", "supers", "self_", "SELF"] {
+            assert_eq!(Name::resolve(text).as_path_keyword(), None, "{text}");
+        }
+        assert_eq!(Name::new_tuple_field(0).as_path_keyword(), None);
+    }
+
+    #[test]
+    fn case_insensitive_comparison() {
+        let vec = Name::resolve("Vec");
+        assert!(vec.eq_ignore_case("vec"));
+        assert!(vec.eq_ignore_case("VEC"));
+        assert!(vec.eq_ignore_case("Vec"));
+        assert!(!vec.eq_ignore_case("vecs"));
+        assert!(!vec.eq_ignore_case("Ve"));
+        assert_ne!(vec, Name::resolve("vec"));
+
+        assert!(Name::resolve("r#type").eq_ignore_case("Type"));
+        assert!(Name::new_tuple_field(1).eq_ignore_case("1"));
+        assert!(!Name::new_tuple_field(1).eq_ignore_case("one"));
+    }
+
+    #[test]
+    fn qualified_labels_are_escaped() {
+        let trait_name = Name::resolve("r#type");
+        assert_eq!(Name::resolve("r#fn").qualified_label(&trait_name), "r#type::r#fn");
+        assert_eq!(Name::resolve("next").qualified_label(&trait_name), "r#type::next");
+        // Unnecessarily raw identifiers are not escaped.
+        assert_eq!(
+            Name::resolve("r#next").qualified_label(&Name::resolve("r#Iterator")),
+            "Iterator::next"
+        );
+    }
+
+    #[test]
+    fn sanitized_names() {
+        assert_eq!(Name::sanitize("hello world!").to_string(), "hello_world_");
+        assert!(Name::is_sanitized("hello world!"));
+
+        assert_eq!(Name::sanitize("1st").to_string(), "_1st");
+        assert!(Name::is_sanitized("1st"));
+        assert_eq!(Name::sanitize("").to_string(), "_");
+        assert_eq!(Name::sanitize("_").to_string(), "__");
+
+        // Valid identifiers are kept, but escaped if needed.
+        assert_eq!(Name::sanitize("valid_ident"), Name::resolve("valid_ident"));
+        assert!(!Name::is_sanitized("valid_ident"));
+        assert_eq!(Name::sanitize("fn").to_string(), "r#fn");
+
+        // The result is an ordinary name, equal to the same identifier written in the source.
+        assert_eq!(Name::sanitize("1st"), Name::resolve("_1st"));
+    }
+
+    #[test]
+    fn truncated_display() {
+        let long = Name::resolve("a_very_long_generated_identifier");
+        assert_eq!(long.display_truncated(10), "a_very_lo…");
+        assert_eq!(long.display_truncated(10).chars().count(), 10);
+        assert_eq!(long.display_truncated(100), "a_very_long_generated_identifier");
+
+        let short = Name::resolve("short");
+        assert_eq!(short.display_truncated(5), "short");
+
+        // Multibyte characters are never split.
+        let multibyte = Name::resolve("ééééé");
+        assert_eq!(multibyte.display_truncated(3), "éé…");
+        assert_eq!(multibyte.display_truncated(5), "ééééé");
+
+        // Neither are combining marks split off from their base character.
+        let combining = Name::resolve("e\u{301}e\u{301}e\u{301}");
+        assert_eq!(combining.display_truncated(2), "e\u{301}…");
+        assert_eq!(combining.display_truncated(3), "e\u{301}e\u{301}e\u{301}");
+
+        assert_eq!(long.display_truncated(0), "");
+        assert_eq!(long.display_truncated(1), "…");
+    }
+
+    #[test]
+    fn lifetime_names() {
+        let file = SourceFile::parse("fn f<'a>(x: &'a u8) {}").tree();
+        let lifetime = file.syntax().descendants().find_map(ast::Lifetime::cast).unwrap();
+        let name = Name::from_ast_lifetime(&lifetime);
+        assert_eq!(name.to_string(), "'a");
+        assert!(name.is_lifetime());
+        assert!(known::STATIC_LIFETIME.is_lifetime());
+
+        assert_ne!(name, Name::resolve("a"));
+        assert!(!Name::resolve("a").is_lifetime());
+        assert!(!Name::new_tuple_field(0).is_lifetime());
+    }
+
+    #[test]
+    fn name_round_trips_through_id() {
+        let db = TestDB::default();
+        let foo = ast::make::name("foo").as_name();
+        let bar = ast::make::name("bar").as_name();
+        let field = Name::new_tuple_field(0);
+
+        let ids = [foo.as_id(&db), bar.as_id(&db), field.as_id(&db)];
+        assert_eq!(foo.as_id(&db), ids[0]);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+
+        assert_eq!(Name::from_id(&db, ids[0]), foo);
+        assert_eq!(Name::from_id(&db, ids[1]), bar);
+        assert_eq!(Name::from_id(&db, ids[2]), field);
+    }
+}
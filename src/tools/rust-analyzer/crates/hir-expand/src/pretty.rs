@@ -0,0 +1,185 @@
+//! Pretty-printing of macro expansions, both as syntax tokens and as token trees, for snapshot
+//! tests.
+
+use std::fmt;
+
+use stdx::format_to;
+use syntax::{
+    SyntaxKind::{self, *},
+    T,
+};
+
+/// Prints `tokens`, given as their kind and text, with rustc-like spacing, placing blocks and
+/// statements on lines of their own.
+pub fn print_tokens<D: fmt::Display>(tokens: impl IntoIterator<Item = (SyntaxKind, D)>) -> String {
+    let mut res = String::new();
+    let mut prev_kind = EOF;
+    let mut indent_level = 0;
+    for (curr_kind, text) in tokens {
+        let space = match (prev_kind, curr_kind) {
+            _ if prev_kind.is_trivia() || curr_kind.is_trivia() => "",
+            (T!['{'], T!['}']) => "",
+            (T![=], _) | (_, T![=]) => " ",
+            (_, T!['{']) => " ",
+            (T![;] | T!['{'] | T!['}'], _) => "\n",
+            (_, T!['}']) => "\n",
+            (IDENT | LIFETIME_IDENT, IDENT | LIFETIME_IDENT) => " ",
+            _ if prev_kind.is_keyword() && curr_kind.is_keyword() => " ",
+            (IDENT, _) if curr_kind.is_keyword() => " ",
+            (_, IDENT) if prev_kind.is_keyword() => " ",
+            (T![>], IDENT) => " ",
+            (T![>], _) if curr_kind.is_keyword() => " ",
+            (T![->], _) | (_, T![->]) => " ",
+            (T![&&], _) | (_, T![&&]) => " ",
+            (T![,], _) => " ",
+            (T![:], IDENT | T!['(']) => " ",
+            (T![:], _) if curr_kind.is_keyword() => " ",
+            (T![fn], T!['(']) => "",
+            (T![']'], _) if curr_kind.is_keyword() => " ",
+            (T![']'], T![#]) => "\n",
+            (T![Self], T![::]) => "",
+            _ if prev_kind.is_keyword() => " ",
+            _ => "",
+        };
+
+        match prev_kind {
+            T!['{'] => indent_level += 1,
+            T!['}'] => indent_level -= 1,
+            _ => (),
+        }
+
+        res.push_str(space);
+        if space == "\n" {
+            let level = if curr_kind == T!['}'] { indent_level - 1 } else { indent_level };
+            res.push_str(&"    ".repeat(level));
+        }
+        prev_kind = curr_kind;
+        format_to!(res, "{}", text);
+    }
+    res
+}
+
+/// Prints the token tree `tt` like [`print_tokens`] prints the syntax tokens it stands for.
+///
+/// Joint punctuation is glued into the compound tokens the parser would make of it, like `->`,
+/// but the tree is never actually parsed.
+pub fn print_subtree(tt: &tt::Subtree) -> String {
+    let mut tokens = Vec::new();
+    flatten_subtree(tt, &mut tokens);
+    print_tokens(tokens)
+}
+
+fn flatten_subtree(subtree: &tt::Subtree, acc: &mut Vec<(SyntaxKind, String)>) {
+    let delimiters = subtree.delimiter_kind().map(|kind| match kind {
+        tt::DelimiterKind::Parenthesis => ((T!['('], "("), (T![')'], ")")),
+        tt::DelimiterKind::Brace => ((T!['{'], "{"), (T!['}'], "}")),
+        tt::DelimiterKind::Bracket => ((T!['['], "["), (T![']'], "]")),
+    });
+    if let Some(((kind, text), _)) = delimiters {
+        acc.push((kind, text.to_string()));
+    }
+
+    let mut token_trees = subtree.token_trees.iter().peekable();
+    while let Some(token_tree) = token_trees.next() {
+        let leaf = match token_tree {
+            tt::TokenTree::Subtree(it) => {
+                flatten_subtree(it, acc);
+                continue;
+            }
+            tt::TokenTree::Leaf(it) => it,
+        };
+        match leaf {
+            tt::Leaf::Literal(lit) => acc.push((LITERAL, lit.text.to_string())),
+            tt::Leaf::Ident(ident) => {
+                let kind = match &*ident.text {
+                    "_" => T![_],
+                    text if text.starts_with('\'') => LIFETIME_IDENT,
+                    text => SyntaxKind::from_keyword(text).unwrap_or(IDENT),
+                };
+                acc.push((kind, ident.text.to_string()));
+            }
+            tt::Leaf::Punct(punct) => {
+                let mut text = punct.char.to_string();
+                let mut spacing = punct.spacing;
+                while spacing == tt::Spacing::Joint {
+                    match token_trees.peek() {
+                        Some(tt::TokenTree::Leaf(tt::Leaf::Punct(next))) => {
+                            text.push(next.char);
+                            spacing = next.spacing;
+                            token_trees.next();
+                        }
+                        // A lifetime is a quote joint to an identifier.
+                        Some(tt::TokenTree::Leaf(tt::Leaf::Ident(next)))
+                            if text.ends_with('\'') =>
+                        {
+                            text.push_str(&next.text);
+                            token_trees.next();
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                glue_puncts(&text, acc);
+            }
+        }
+    }
+
+    if let Some((_, (kind, text))) = delimiters {
+        acc.push((kind, text.to_string()));
+    }
+}
+
+/// Splits a run of joint punctuation, which may end in a lifetime, into the longest compound
+/// tokens it starts with.
+fn glue_puncts(mut text: &str, acc: &mut Vec<(SyntaxKind, String)>) {
+    let mut lifetime = None;
+    if let Some(idx) = text.find('\'') {
+        if idx + 1 < text.len() {
+            lifetime = Some(&text[idx..]);
+            text = &text[..idx];
+        }
+    }
+    while let Some(first) = text.chars().next() {
+        let (kind, len) = match text.get(..3).and_then(compound_punct) {
+            Some(kind) => (kind, 3),
+            None => match text.get(..2).and_then(compound_punct) {
+                Some(kind) => (kind, 2),
+                None => (SyntaxKind::from_char(first).unwrap_or(ERROR), first.len_utf8()),
+            },
+        };
+        acc.push((kind, text[..len].to_string()));
+        text = &text[len..];
+    }
+    acc.extend(lifetime.map(|it| (LIFETIME_IDENT, it.to_string())));
+}
+
+fn compound_punct(text: &str) -> Option<SyntaxKind> {
+    let kind = match text {
+        "..." => T![...],
+        "..=" => T![..=],
+        "<<=" => T![<<=],
+        ">>=" => T![>>=],
+        ".." => T![..],
+        "::" => T![::],
+        "==" => T![==],
+        "=>" => T![=>],
+        "!=" => T![!=],
+        "->" => T![->],
+        "<=" => T![<=],
+        ">=" => T![>=],
+        "+=" => T![+=],
+        "-=" => T![-=],
+        "|=" => T![|=],
+        "&=" => T![&=],
+        "^=" => T![^=],
+        "/=" => T![/=],
+        "*=" => T![*=],
+        "%=" => T![%=],
+        "&&" => T![&&],
+        "||" => T![||],
+        "<<" => T![<<],
+        ">>" => T![>>],
+        _ => return None,
+    };
+    Some(kind)
+}
@@ -0,0 +1,94 @@
+//! Database used for testing `hir_expand`.
+
+use std::{fmt, panic, sync::Arc};
+
+use base_db::{
+    fixture::WithFixture, salsa, AnchoredPath, CrateId, FileId, FileLoader, FileLoaderDelegate,
+};
+use stdx::hash::NoHashHashSet;
+use syntax::{
+    ast::{self, HasName},
+    AstNode,
+};
+
+use crate::{
+    db::{AstDatabase, ExpansionProvenanceLog, HasExpansionProvenance},
+    ExpandTo, InFile, MacroCallId, MacroCallKind, MacroDefId, MacroDefKind,
+};
+
+#[salsa::database(
+    base_db::SourceDatabaseExtStorage,
+    base_db::SourceDatabaseStorage,
+    crate::db::AstDatabaseStorage
+)]
+#[derive(Default)]
+pub(crate) struct TestDB {
+    storage: salsa::Storage<TestDB>,
+    expansion_provenance: Arc<ExpansionProvenanceLog>,
+}
+
+impl HasExpansionProvenance for TestDB {
+    fn expansion_provenance_log(&self) -> &ExpansionProvenanceLog {
+        &self.expansion_provenance
+    }
+}
+
+impl salsa::Database for TestDB {}
+
+impl fmt::Debug for TestDB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestDB").finish()
+    }
+}
+
+impl panic::RefUnwindSafe for TestDB {}
+
+impl FileLoader for TestDB {
+    fn file_text(&self, file_id: FileId) -> Arc<String> {
+        FileLoaderDelegate(self).file_text(file_id)
+    }
+    fn resolve_path(&self, path: AnchoredPath<'_>) -> Option<FileId> {
+        FileLoaderDelegate(self).resolve_path(path)
+    }
+    fn relevant_crates(&self, file_id: FileId) -> Arc<NoHashHashSet<CrateId>> {
+        FileLoaderDelegate(self).relevant_crates(file_id)
+    }
+}
+
+impl TestDB {
+    /// Returns the fn-like macro calls of `file_id` in source order, each resolved to the
+    /// declarative macro of the same name defined in that file.
+    ///
+    /// There is no name resolution at this layer, so this ignores paths, scopes and textual order.
+    /// Tests that need any of that belong to `hir_def`.
+    pub(crate) fn macro_calls(
+        &self,
+        file_id: FileId,
+    ) -> Vec<(InFile<ast::MacroCall>, MacroCallId)> {
+        let krate = self.test_crate();
+        let file_id = file_id.into();
+        let root = self.parse_or_expand(file_id).unwrap();
+        let ast_id_map = self.ast_id_map(file_id);
+        let macros: Vec<_> = root.descendants().filter_map(ast::Macro::cast).collect();
+        root.descendants()
+            .filter_map(ast::MacroCall::cast)
+            .map(|call| {
+                let name = call.path().and_then(|it| it.segment()?.name_ref()).unwrap();
+                let mac = macros
+                    .iter()
+                    .find(|it| it.name().map_or(false, |it| it.text() == name.text()))
+                    .unwrap();
+                let def = MacroDefId {
+                    krate,
+                    kind: MacroDefKind::Declarative(InFile::new(file_id, ast_id_map.ast_id(mac))),
+                    local_inner: false,
+                };
+                let kind = MacroCallKind::FnLike {
+                    ast_id: InFile::new(file_id, ast_id_map.ast_id(&call)),
+                    expand_to: ExpandTo::from_call_site(&call),
+                };
+                (InFile::new(file_id, call), def.as_lazy_macro(self, krate, kind))
+            })
+            .collect()
+    }
+}
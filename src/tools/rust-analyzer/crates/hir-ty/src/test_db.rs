@@ -9,7 +9,7 @@
     salsa, AnchoredPath, CrateId, FileId, FileLoader, FileLoaderDelegate, SourceDatabase, Upcast,
 };
 use hir_def::{db::DefDatabase, ModuleId};
-use hir_expand::db::AstDatabase;
+use hir_expand::db::{AstDatabase, ExpansionProvenanceLog, HasExpansionProvenance};
 use stdx::hash::{NoHashHashMap, NoHashHashSet};
 use syntax::TextRange;
 use test_utils::extract_annotations;
@@ -25,11 +25,16 @@
 pub(crate) struct TestDB {
     storage: salsa::Storage<TestDB>,
     events: Mutex<Option<Vec<salsa::Event>>>,
+    expansion_provenance: Arc<ExpansionProvenanceLog>,
 }
 
 impl Default for TestDB {
     fn default() -> Self {
-        let mut this = Self { storage: Default::default(), events: Default::default() };
+        let mut this = Self {
+            storage: Default::default(),
+            events: Default::default(),
+            expansion_provenance: Default::default(),
+        };
         this.set_enable_proc_attr_macros(true);
         this
     }
@@ -53,6 +58,12 @@ fn upcast(&self) -> &(dyn DefDatabase + 'static) {
     }
 }
 
+impl HasExpansionProvenance for TestDB {
+    fn expansion_provenance_log(&self) -> &ExpansionProvenanceLog {
+        &self.expansion_provenance
+    }
+}
+
 impl salsa::Database for TestDB {
     fn salsa_event(&self, event: salsa::Event) {
         let mut events = self.events.lock().unwrap();
@@ -67,6 +78,7 @@ fn snapshot(&self) -> salsa::Snapshot<TestDB> {
         salsa::Snapshot::new(TestDB {
             storage: self.storage.snapshot(),
             events: Default::default(),
+            expansion_provenance: self.expansion_provenance.clone(),
         })
     }
 }
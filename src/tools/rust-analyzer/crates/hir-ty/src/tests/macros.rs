@@ -3,7 +3,7 @@
 
 use crate::tests::check_infer_with_mismatches;
 
-use super::{check_infer, check_types};
+use super::{check, check_infer, check_types};
 
 #[test]
 fn cfg_impl_def() {
@@ -724,6 +724,58 @@ fn main() {
     );
 }
 
+#[test]
+fn infer_builtin_macros_todo() {
+    check(
+        r#"
+mod panic {
+    #[macro_export]
+    pub macro panic_2015 {
+        ($($t:tt)*) => ( $crate::panicking::panic() ),
+    }
+    #[macro_export]
+    pub macro panic_2021 {
+        ($($t:tt)*) => ( $crate::panicking::panic() ),
+    }
+}
+
+mod panicking {
+    pub fn panic() -> ! { loop {} }
+}
+
+#[rustc_builtin_macro]
+macro_rules! todo {
+    ($($arg:tt)*) => { /* compiler built-in */ };
+}
+
+#[rustc_builtin_macro]
+macro_rules! unimplemented {
+    ($($arg:tt)*) => { /* compiler built-in */ };
+}
+
+fn with_message() -> u32 {
+    let x = todo!("with a {}", "message");
+    x;
+  //^ type: !
+    let y = unimplemented!();
+    y;
+  //^ type: !
+    0
+}
+
+fn tail() -> u32 {
+    todo!()
+}
+
+fn block_tail() -> u32 {
+    let x = { todo!() };
+    x
+  //^ type: !
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_builtin_macros_include() {
     check_types(
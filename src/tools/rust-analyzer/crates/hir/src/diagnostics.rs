@@ -101,6 +101,8 @@ pub struct MacroError {
     pub node: InFile<SyntaxNodePtr>,
     pub precise_location: Option<TextRange>,
     pub message: String,
+    /// Whether this is the message of a `compile_error!` invocation.
+    pub is_compile_error: bool,
 }
 
 #[derive(Debug)]
@@ -709,9 +709,17 @@ fn emit_def_diagnostic(db: &dyn HirDatabase, acc: &mut Vec<AnyDiagnostic>, diag:
             );
         }
 
-        DefDiagnosticKind::MacroError { ast, message } => {
+        DefDiagnosticKind::MacroError { ast, message, is_compile_error } => {
             let (node, precise_location, _, _) = precise_macro_call_location(ast, db);
-            acc.push(MacroError { node, precise_location, message: message.clone() }.into());
+            acc.push(
+                MacroError {
+                    node,
+                    precise_location,
+                    message: message.clone(),
+                    is_compile_error: *is_compile_error,
+                }
+                .into(),
+            );
         }
 
         DefDiagnosticKind::UnimplementedBuiltinMacro { ast } => {
@@ -1297,11 +1305,12 @@ pub fn diagnostics(self, db: &dyn HirDatabase, acc: &mut Vec<AnyDiagnostic>) {
                     InactiveCode { node: node.clone(), cfg: cfg.clone(), opts: opts.clone() }
                         .into(),
                 ),
-                BodyDiagnostic::MacroError { node, message } => acc.push(
+                BodyDiagnostic::MacroError { node, message, is_compile_error } => acc.push(
                     MacroError {
                         node: node.clone().map(|it| it.into()),
                         precise_location: None,
                         message: message.to_string(),
+                        is_compile_error: *is_compile_error,
                     }
                     .into(),
                 ),
@@ -2047,6 +2056,20 @@ pub fn is_builtin_derive(&self, db: &dyn HirDatabase) -> bool {
         }
     }
 
+    /// Returns whether this is one of the builtin inline assembly macros, whose arguments we
+    /// don't model.
+    pub fn is_asm(&self, db: &dyn HirDatabase) -> bool {
+        match self.id {
+            MacroId::Macro2Id(it) => {
+                matches!(it.lookup(db.upcast()).expander, MacroExpander::BuiltIn(e) if e.is_asm())
+            }
+            MacroId::MacroRulesId(it) => {
+                matches!(it.lookup(db.upcast()).expander, MacroExpander::BuiltIn(e) if e.is_asm())
+            }
+            MacroId::ProcMacroId(_) => false,
+        }
+    }
+
     pub fn is_attr(&self, db: &dyn HirDatabase) -> bool {
         matches!(self.kind(db), MacroKind::Attr)
     }
@@ -143,6 +143,8 @@ pub fn parse_or_expand(&self, file_id: HirFileId) -> Option<SyntaxNode> {
         self.imp.parse_or_expand(file_id)
     }
 
+    /// Returns `None` for special macros like `asm!`, which are not expanded like ordinary
+    /// token trees.
     pub fn expand(&self, macro_call: &ast::MacroCall) -> Option<SyntaxNode> {
         self.imp.expand(macro_call)
     }
@@ -515,7 +517,11 @@ fn parse_or_expand(&self, file_id: HirFileId) -> Option<SyntaxNode> {
 
     fn expand(&self, macro_call: &ast::MacroCall) -> Option<SyntaxNode> {
         let sa = self.analyze_no_infer(macro_call.syntax())?;
-        let file_id = sa.expand(self.db, InFile::new(sa.file_id, macro_call))?;
+        let macro_call = InFile::new(sa.file_id, macro_call);
+        if sa.is_special_macro_call(self.db, macro_call) {
+            return None;
+        }
+        let file_id = sa.expand(self.db, macro_call)?;
         let node = self.parse_or_expand(file_id)?;
         Some(node)
     }
@@ -1479,7 +1485,9 @@ fn is_inside_unsafe(&self, expr: &ast::Expr) -> bool {
                 ast::Variant::cast(ancestor).map(Either::Right)
             }
         };
-        let Some(enclosing_item) = expr.syntax().ancestors().find_map(item_or_variant) else { return false };
+        let Some(enclosing_item) = expr.syntax().ancestors().find_map(item_or_variant) else {
+            return false;
+        };
 
         let def = match &enclosing_item {
             Either::Left(ast::Item::Fn(it)) if it.unsafe_token().is_some() => return true,
@@ -1528,17 +1536,9 @@ fn macro_call_to_macro_id(
     macro_call_id: MacroCallId,
 ) -> Option<MacroId> {
     let loc = db.lookup_intern_macro_call(macro_call_id);
-    match loc.def.kind {
-        hir_expand::MacroDefKind::Declarative(it)
-        | hir_expand::MacroDefKind::BuiltIn(_, it)
-        | hir_expand::MacroDefKind::BuiltInAttr(_, it)
-        | hir_expand::MacroDefKind::BuiltInDerive(_, it)
-        | hir_expand::MacroDefKind::BuiltInEager(_, it) => {
-            ctx.macro_to_def(InFile::new(it.file_id, it.to_node(db)))
-        }
-        hir_expand::MacroDefKind::ProcMacro(_, _, it) => {
-            ctx.proc_macro_to_def(InFile::new(it.file_id, it.to_node(db)))
-        }
+    match loc.def.ast_id() {
+        Either::Left(it) => ctx.macro_to_def(InFile::new(it.file_id, it.to_node(db))),
+        Either::Right(it) => ctx.proc_macro_to_def(InFile::new(it.file_id, it.to_node(db))),
     }
 }
 
@@ -25,7 +25,6 @@
     LocalFieldId, Lookup, ModuleDefId, TraitId, VariantId,
 };
 use hir_expand::{
-    builtin_fn_macro::BuiltinFnLikeExpander,
     hygiene::Hygiene,
     mod_path::path,
     name,
@@ -730,26 +729,25 @@ pub(crate) fn resolve_variant(
         infer.variant_resolution_for_expr(expr_id)
     }
 
+    /// Returns whether `macro_call` resolves to a builtin with a grammar of its own, like `asm!`.
+    ///
+    /// These are only expanded to a stand-in, which must not be mistaken for their expansion.
+    pub(crate) fn is_special_macro_call(
+        &self,
+        db: &dyn HirDatabase,
+        macro_call: InFile<&ast::MacroCall>,
+    ) -> bool {
+        self.resolve_macro_call(db, macro_call).map_or(false, |mac| mac.is_asm(db))
+    }
+
     pub(crate) fn is_unsafe_macro_call(
         &self,
         db: &dyn HirDatabase,
         macro_call: InFile<&ast::MacroCall>,
     ) -> bool {
         // check for asm/global_asm
-        if let Some(mac) = self.resolve_macro_call(db, macro_call) {
-            let ex = match mac.id {
-                hir_def::MacroId::Macro2Id(it) => it.lookup(db.upcast()).expander,
-                hir_def::MacroId::MacroRulesId(it) => it.lookup(db.upcast()).expander,
-                _ => hir_def::MacroExpander::Declarative,
-            };
-            match ex {
-                hir_def::MacroExpander::BuiltIn(e)
-                    if e == BuiltinFnLikeExpander::Asm || e == BuiltinFnLikeExpander::GlobalAsm =>
-                {
-                    return true
-                }
-                _ => (),
-            }
+        if self.is_special_macro_call(db, macro_call) {
+            return true;
         }
         let macro_expr = match macro_call
             .map(|it| it.syntax().parent().and_then(ast::MacroExpr::cast))
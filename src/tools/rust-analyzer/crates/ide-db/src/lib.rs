@@ -50,7 +50,7 @@ pub mod syntax_helpers {
     AnchoredPath, CrateId, FileId, FileLoader, FileLoaderDelegate, SourceDatabase, Upcast,
 };
 use hir::{
-    db::{AstDatabase, DefDatabase, HirDatabase},
+    db::{AstDatabase, DefDatabase, ExpansionProvenanceLog, HasExpansionProvenance, HirDatabase},
     symbols::FileSymbolKind,
 };
 use stdx::hash::NoHashHashSet;
@@ -81,6 +81,8 @@ pub struct RootDatabase {
     // which duplicates `Weak::drop` and `Arc::drop` tens of thousands of times, which makes
     // compile times of all `ide_*` and downstream crates suffer greatly.
     storage: ManuallyDrop<salsa::Storage<RootDatabase>>,
+    /// Shared with all snapshots, cleared in [`RootDatabase::apply_change`].
+    expansion_provenance: Arc<ExpansionProvenanceLog>,
 }
 
 impl Drop for RootDatabase {
@@ -125,6 +127,12 @@ fn relevant_crates(&self, file_id: FileId) -> Arc<NoHashHashSet<CrateId>> {
     }
 }
 
+impl HasExpansionProvenance for RootDatabase {
+    fn expansion_provenance_log(&self) -> &ExpansionProvenanceLog {
+        &self.expansion_provenance
+    }
+}
+
 impl salsa::Database for RootDatabase {}
 
 impl Default for RootDatabase {
@@ -135,7 +143,10 @@ fn default() -> RootDatabase {
 
 impl RootDatabase {
     pub fn new(lru_capacity: Option<usize>) -> RootDatabase {
-        let mut db = RootDatabase { storage: ManuallyDrop::new(salsa::Storage::default()) };
+        let mut db = RootDatabase {
+            storage: ManuallyDrop::new(salsa::Storage::default()),
+            expansion_provenance: Default::default(),
+        };
         db.set_crate_graph_with_durability(Default::default(), Durability::HIGH);
         db.set_local_roots_with_durability(Default::default(), Durability::HIGH);
         db.set_library_roots_with_durability(Default::default(), Durability::HIGH);
@@ -154,7 +165,10 @@ pub fn update_lru_capacity(&mut self, lru_capacity: Option<usize>) {
 
 impl salsa::ParallelDatabase for RootDatabase {
     fn snapshot(&self) -> salsa::Snapshot<RootDatabase> {
-        salsa::Snapshot::new(RootDatabase { storage: ManuallyDrop::new(self.storage.snapshot()) })
+        salsa::Snapshot::new(RootDatabase {
+            storage: ManuallyDrop::new(self.storage.snapshot()),
+            expansion_provenance: self.expansion_provenance.clone(),
+        })
     }
 }
 
@@ -1,16 +1,47 @@
+use itertools::Itertools;
+use syntax::{ast, AstNode};
+
 use crate::{Diagnostic, DiagnosticsContext};
 
 // Diagnostic: macro-error
 //
 // This diagnostic is shown for macro expansion errors.
+//
+// Messages of `compile_error!` invocations are the macro author's own, so unlike other expansion
+// errors these are not experimental.
 pub(crate) fn macro_error(ctx: &DiagnosticsContext<'_>, d: &hir::MacroError) -> Diagnostic {
     // Use more accurate position if available.
     let display_range = ctx.resolve_precise_location(&d.node, d.precise_location);
-    Diagnostic::new("macro-error", d.message.clone(), display_range).experimental()
+    let message = match macro_backtrace(ctx, d) {
+        Some(backtrace) => format!("{} (in this macro invocation: {backtrace})", d.message),
+        None => d.message.clone(),
+    };
+    let diagnostic = Diagnostic::new("macro-error", message, display_range);
+    if d.is_compile_error {
+        diagnostic
+    } else {
+        diagnostic.experimental()
+    }
+}
+
+/// Renders the macros the failing call was expanded from, outermost first, if it was.
+fn macro_backtrace(ctx: &DiagnosticsContext<'_>, d: &hir::MacroError) -> Option<String> {
+    let mut stack: Vec<_> =
+        d.node.file_id.macro_stack_trace(ctx.sema.db).iter().map(ToString::to_string).collect();
+    if stack.is_empty() {
+        return None;
+    }
+    let root = ctx.sema.parse_or_expand(d.node.file_id)?;
+    if let Some(call) = ast::MacroCall::cast(d.node.value.to_node(&root)) {
+        stack.extend(call.path().and_then(|it| it.segment()).map(|it| it.syntax().to_string()));
+    }
+    Some(stack.iter().map(|name| format!("{name}!")).join(" -> "))
 }
 
 #[cfg(test)]
 mod tests {
+    use ide_db::{assists::AssistResolveStrategy, base_db::fixture::WithFixture, RootDatabase};
+
     use crate::{
         tests::{check_diagnostics, check_diagnostics_with_config},
         DiagnosticsConfig,
@@ -35,6 +66,32 @@ macro_rules! compile_error { () => {} }
         );
     }
 
+    #[test]
+    fn compile_error_is_not_experimental() {
+        let mut config = DiagnosticsConfig::test_sample();
+        config.disable_experimental = true;
+        check_diagnostics_with_config(
+            config,
+            r#"
+#[rustc_builtin_macro]
+macro_rules! include { () => {} }
+
+#[rustc_builtin_macro]
+macro_rules! compile_error { () => {} }
+
+  include!("doesntexist");
+
+  compile_error!("compile_error macro works");
+//^^^^^^^^^^^^^ error: compile_error macro works
+
+fn f() {
+    compile_error!("in a body");
+  //^^^^^^^^^^^^^ error: in a body
+}
+            "#,
+        );
+    }
+
     #[test]
     fn eager_macro_concat() {
         // FIXME: this is incorrectly handling `$crate`, resulting in a wrong diagnostic.
@@ -74,6 +131,32 @@ macro_rules! concat { () => {} }
         );
     }
 
+    #[test]
+    fn nested_macro_error_has_backtrace() {
+        let (db, file_id) = RootDatabase::with_single_file(
+            r#"
+macro_rules! inner { (x) => {} }
+macro_rules! outer { () => { inner!(y); } }
+outer!();
+"#,
+        );
+        let diagnostics = crate::diagnostics(
+            &db,
+            &DiagnosticsConfig::test_sample(),
+            &AssistResolveStrategy::All,
+            file_id,
+        );
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .filter(|it| it.code.as_str() == "macro-error")
+            .map(|it| it.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            ["unexpected token in input (in this macro invocation: outer! -> inner!)"]
+        );
+    }
+
     #[test]
     fn include_macro_should_allow_empty_content() {
         let mut config = DiagnosticsConfig::test_sample();
@@ -208,7 +291,7 @@ macro_rules! outer {
 
 fn f() {
     outer!();
-} //^^^^^^^^ error: leftover tokens
+} //^^^^^^^^ error: leftover tokens (in this macro invocation: outer! -> format_args! -> arg!)
 "#,
         )
     }
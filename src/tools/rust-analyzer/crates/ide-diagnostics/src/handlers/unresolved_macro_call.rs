@@ -1,4 +1,16 @@
-use crate::{Diagnostic, DiagnosticsContext};
+use hir::db::AstDatabase;
+use ide_db::{
+    assists::Assist,
+    helpers::mod_path_to_ast,
+    imports::{
+        import_assets::ImportAssets,
+        insert_use::{insert_use, ImportScope},
+    },
+    source_change::SourceChangeBuilder,
+};
+use syntax::{ast, AstNode};
+
+use crate::{fix, Diagnostic, DiagnosticsContext};
 
 // Diagnostic: unresolved-macro-call
 //
@@ -17,11 +29,52 @@ pub(crate) fn unresolved_macro_call(
         display_range,
     )
     .experimental()
+    .with_fixes(fixes(ctx, d))
+}
+
+fn fixes(ctx: &DiagnosticsContext<'_>, d: &hir::UnresolvedMacroCall) -> Option<Vec<Assist>> {
+    // Edits inside macro expansions can't be mapped back reliably, so only offer imports for
+    // calls written in a real file.
+    if d.macro_call.file_id.is_macro() {
+        return None;
+    }
+    let file_id = d.macro_call.file_id.try_original_file(ctx.sema.db)?;
+    let root = ctx.sema.db.parse_or_expand(d.macro_call.file_id)?;
+    let macro_call = ast::MacroCall::cast(d.macro_call.value.to_node(&root))?;
+    let path = macro_call.path()?;
+    let import_assets = ImportAssets::for_exact_path(&path, &ctx.sema)?;
+    let import_scope = ImportScope::find_insert_use_container(macro_call.syntax(), &ctx.sema)?;
+    let range = path.syntax().text_range();
+
+    let fixes: Vec<_> = import_assets
+        .search_for_imports(&ctx.sema, ctx.config.insert_use.prefix_kind, ctx.config.prefer_no_std)
+        .into_iter()
+        .map(|import| {
+            let mut builder = SourceChangeBuilder::new(file_id);
+            let scope = match import_scope.clone() {
+                ImportScope::File(it) => ImportScope::File(builder.make_mut(it)),
+                ImportScope::Module(it) => ImportScope::Module(builder.make_mut(it)),
+                ImportScope::Block(it) => ImportScope::Block(builder.make_mut(it)),
+            };
+            insert_use(&scope, mod_path_to_ast(&import.import_path), &ctx.config.insert_use);
+            fix(
+                "import_macro",
+                &format!("Import `{}`", import.import_path),
+                builder.finish(),
+                range,
+            )
+        })
+        .collect();
+    if fixes.is_empty() {
+        None
+    } else {
+        Some(fixes)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::check_diagnostics;
+    use crate::tests::{check_diagnostics, check_fix};
 
     #[test]
     fn unresolved_macro_diag() {
@@ -67,6 +120,28 @@ macro_rules! m { () => {} } }
 
 self::m!(); self::m2!();
                 //^^ error: unresolved macro `self::m2!`
+"#,
+        );
+    }
+
+    #[test]
+    fn import_unresolved_macro() {
+        check_fix(
+            r#"
+//- /lib.rs crate:main deps:dep
+fn f() {
+    foo$0!();
+}
+//- /dep.rs crate:dep
+#[macro_export]
+macro_rules! foo { () => {} }
+"#,
+            r#"
+use dep::foo;
+
+fn f() {
+    foo!();
+}
 "#,
         );
     }
@@ -153,6 +153,9 @@ pub struct DiagnosticsConfig {
     // FIXME: We may want to include a whole `AssistConfig` here
     pub insert_use: InsertUseConfig,
     pub prefer_no_std: bool,
+    /// Report identical diagnostics (same code, range and message) as many
+    /// times as they were produced, instead of collapsing them into one.
+    pub keep_duplicates: bool,
 }
 
 impl DiagnosticsConfig {
@@ -174,6 +177,7 @@ pub fn test_sample() -> Self {
                 skip_glob_imports: false,
             },
             prefer_no_std: false,
+            keep_duplicates: false,
         }
     }
 }
@@ -281,9 +285,22 @@ pub fn diagnostics(
             && !(ctx.config.disable_experimental && d.experimental)
     });
 
+    if !ctx.config.keep_duplicates {
+        dedup_diagnostics(&mut res);
+    }
+
     res
 }
 
+/// Collapses identical diagnostics into the first one.
+///
+/// A macro invoked repeatedly from generated code can easily report the very
+/// same error, at the very same place in the original file, many times.
+fn dedup_diagnostics(diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = FxHashSet::default();
+    diagnostics.retain(|d| seen.insert((d.code.0, d.range, d.message.clone())));
+}
+
 fn fix(id: &'static str, label: &str, source_change: SourceChange, target: TextRange) -> Assist {
     let mut res = unresolved_fix(id, label, target);
     res.source_change = Some(source_change);
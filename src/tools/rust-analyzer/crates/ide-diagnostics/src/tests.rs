@@ -8,9 +8,10 @@
     RootDatabase,
 };
 use stdx::trim_indent;
+use syntax::TextRange;
 use test_utils::{assert_eq_text, extract_annotations};
 
-use crate::{DiagnosticsConfig, ExprFillDefaultMode, Severity};
+use crate::{dedup_diagnostics, Diagnostic, DiagnosticsConfig, ExprFillDefaultMode, Severity};
 
 /// Takes a multi-file input fixture with annotated cursor positions,
 /// and checks that:
@@ -143,3 +144,45 @@ fn test_disabled_diagnostics() {
     );
     assert!(!diagnostics.is_empty());
 }
+
+#[test]
+fn test_identical_diagnostics_are_deduplicated() {
+    let range = TextRange::new(0.into(), 5.into());
+    let mut diagnostics = vec![
+        Diagnostic::new("macro-error", "boom", range),
+        Diagnostic::new("macro-error", "boom", range),
+        Diagnostic::new("macro-error", "boom", range),
+    ];
+    dedup_diagnostics(&mut diagnostics);
+    assert_eq!(diagnostics.len(), 1);
+
+    let mut diagnostics = vec![
+        Diagnostic::new("macro-error", "boom", range),
+        Diagnostic::new("macro-error", "bang", range),
+        Diagnostic::new("unresolved-macro-call", "boom", range),
+        Diagnostic::new("macro-error", "boom", TextRange::new(6.into(), 10.into())),
+    ];
+    dedup_diagnostics(&mut diagnostics);
+    assert_eq!(diagnostics.len(), 4);
+}
+
+#[test]
+fn test_diagnostics_repeated_by_a_macro_are_deduplicated() {
+    let (db, file_id) = RootDatabase::with_single_file(
+        r#"
+macro_rules! twice {
+    () => { unresolved!(); unresolved!(); };
+}
+twice!();
+"#,
+    );
+    let mut config = DiagnosticsConfig::test_sample();
+
+    let diagnostics = super::diagnostics(&db, &config, &AssistResolveStrategy::All, file_id);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].code.as_str(), "unresolved-macro-call");
+
+    config.keep_duplicates = true;
+    let diagnostics = super::diagnostics(&db, &config, &AssistResolveStrategy::All, file_id);
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+}
@@ -491,6 +491,21 @@ impl < >core::marker::Copy for Foo< >{}
         );
     }
 
+    #[test]
+    fn macro_expand_asm_is_unsupported() {
+        let (analysis, pos) = fixture::position(
+            r#"
+#[rustc_builtin_macro]
+macro_rules! asm {() => {}}
+
+fn f() {
+    unsafe { as$0m!("mov {0}, {1}", out(reg) x, in(reg) y); }
+}
+"#,
+        );
+        assert!(analysis.expand_macro(pos).unwrap().is_none());
+    }
+
     #[test]
     fn macro_expand_derive_multi() {
         check(
@@ -14,18 +14,56 @@ pub(crate) fn expand_rules(
     rules: &[crate::Rule],
     input: &tt::Subtree,
 ) -> ExpandResult<tt::Subtree> {
-    let mut match_: Option<(matcher::Match, &crate::Rule)> = None;
-    for rule in rules {
+    match select_rule(rules, input) {
+        Some((_, match_, ExpandResult { value, err: transcribe_err })) => {
+            ExpandResult { value, err: match_.err.or(transcribe_err) }
+        }
+        None => ExpandResult::only_err(ExpandError::NoMatchingRule),
+    }
+}
+
+/// Returns the index of the rule `input` gets expanded with, and what each of its metavariables
+/// got bound to.
+///
+/// If no rule matches, returns the index of the rule that came closest, if any, and the error it
+/// failed to match with.
+pub(crate) fn match_rules(
+    rules: &[crate::Rule],
+    input: &tt::Subtree,
+) -> Result<(usize, Vec<(SmolStr, tt::Subtree)>), (Option<usize>, ExpandError)> {
+    let (idx, match_, _) = select_rule(rules, input).ok_or((None, ExpandError::NoMatchingRule))?;
+    if let Some(err) = match_.err {
+        return Err((Some(idx), err));
+    }
+    let mut bindings: Vec<_> = match_
+        .bindings
+        .inner
+        .iter()
+        .map(|(name, binding)| (name.clone(), binding.to_subtree()))
+        .collect();
+    bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok((idx, bindings))
+}
+
+/// Picks the rule to expand `input` with, alongside its match and transcription.
+///
+/// This is the first rule that matches and transcribes without errors, or failing that the rule
+/// that came closest to matching. Returns `None` only if there are no rules.
+fn select_rule(
+    rules: &[crate::Rule],
+    input: &tt::Subtree,
+) -> Option<(usize, matcher::Match, ExpandResult<tt::Subtree>)> {
+    let mut match_: Option<(matcher::Match, usize)> = None;
+    for (idx, rule) in rules.iter().enumerate() {
         let new_match = matcher::match_(&rule.lhs, input);
 
         if new_match.err.is_none() {
             // If we find a rule that applies without errors, we're done.
             // Unconditionally returning the transcription here makes the
             // `test_repeat_bad_var` test fail.
-            let ExpandResult { value, err: transcribe_err } =
-                transcriber::transcribe(&rule.rhs, &new_match.bindings);
-            if transcribe_err.is_none() {
-                return ExpandResult::ok(value);
+            let transcribed = transcriber::transcribe(&rule.rhs, &new_match.bindings);
+            if transcribed.err.is_none() {
+                return Some((idx, new_match, transcribed));
             }
         }
         // Use the rule if we matched more tokens, or bound variables count
@@ -33,20 +71,16 @@ pub(crate) fn expand_rules(
             if (new_match.unmatched_tts, -(new_match.bound_count as i32))
                 < (prev_match.unmatched_tts, -(prev_match.bound_count as i32))
             {
-                match_ = Some((new_match, rule));
+                match_ = Some((new_match, idx));
             }
         } else {
-            match_ = Some((new_match, rule));
+            match_ = Some((new_match, idx));
         }
     }
-    if let Some((match_, rule)) = match_ {
-        // if we got here, there was no match without errors
-        let ExpandResult { value, err: transcribe_err } =
-            transcriber::transcribe(&rule.rhs, &match_.bindings);
-        ExpandResult { value, err: match_.err.or(transcribe_err) }
-    } else {
-        ExpandResult::only_err(ExpandError::NoMatchingRule)
-    }
+    // if we got here, there was no match without errors
+    let (match_, idx) = match_?;
+    let transcribed = transcriber::transcribe(&rules[idx].rhs, &match_.bindings);
+    Some((idx, match_, transcribed))
 }
 
 /// The actual algorithm for expansion is not too hard, but is pretty tricky.
@@ -120,3 +154,20 @@ enum Fragment {
     /// tricky to handle in the parser, and rustc doesn't handle those either.
     Expr(tt::TokenTree),
 }
+
+impl Binding {
+    /// Flattens the binding into a single subtree, with each repetition wrapped in an invisible
+    /// delimiter group.
+    fn to_subtree(&self) -> tt::Subtree {
+        let token_trees = match self {
+            Binding::Fragment(
+                Fragment::Tokens(tt::TokenTree::Subtree(subtree))
+                | Fragment::Expr(tt::TokenTree::Subtree(subtree)),
+            ) => return subtree.clone(),
+            Binding::Fragment(Fragment::Tokens(tt) | Fragment::Expr(tt)) => vec![tt.clone()],
+            Binding::Nested(nested) => nested.iter().map(|it| it.to_subtree().into()).collect(),
+            Binding::Empty | Binding::Missing(_) => Vec::new(),
+        };
+        tt::Subtree { delimiter: None, token_trees }
+    }
+}
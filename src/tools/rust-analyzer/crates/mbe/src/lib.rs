@@ -20,6 +20,8 @@
 
 use std::fmt;
 
+use syntax::SmolStr;
+
 use crate::{
     parser::{MetaTemplate, MetaVarKind, Op},
     tt_iter::TtIter,
@@ -107,6 +109,25 @@ pub struct DeclarativeMacro {
     shift: Shift,
 }
 
+/// The rule of a [`DeclarativeMacro`] an input is expanded with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedArm {
+    /// Index of the rule in the macro definition.
+    pub index: usize,
+    /// The tokens each metavariable got bound to, sorted by metavariable name. Repeated
+    /// metavariables get one invisible delimiter group per repetition.
+    pub bindings: Vec<(SmolStr, tt::Subtree)>,
+}
+
+/// Why an input matched none of the rules of a [`DeclarativeMacro`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoMatchingArm {
+    /// Index of the rule that came closest to matching, `None` if the macro has no rules.
+    pub closest_index: Option<usize>,
+    /// The error matching the closest rule failed with.
+    pub err: ExpandError,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Rule {
     lhs: MetaTemplate,
@@ -251,6 +272,20 @@ pub fn expand(&self, tt: &tt::Subtree) -> ExpandResult<tt::Subtree> {
         expander::expand_rules(&self.rules, &tt)
     }
 
+    /// Picks the rule `tt` gets expanded with by [`DeclarativeMacro::expand`], and returns what
+    /// its metavariables got bound to.
+    ///
+    /// Rules whose transcription fails are skipped like when expanding, so the candidates still
+    /// get transcribed, but the transcription is not returned.
+    pub fn match_arm(&self, tt: &tt::Subtree) -> Result<MatchedArm, NoMatchingArm> {
+        let mut tt = tt.clone();
+        self.shift.shift_all(&mut tt);
+        match expander::match_rules(&self.rules, &tt) {
+            Ok((index, bindings)) => Ok(MatchedArm { index, bindings }),
+            Err((closest_index, err)) => Err(NoMatchingArm { closest_index, err }),
+        }
+    }
+
     pub fn map_id_down(&self, id: tt::TokenId) -> tt::TokenId {
         self.shift.shift(id)
     }
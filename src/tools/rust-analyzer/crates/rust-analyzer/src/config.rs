@@ -1029,6 +1029,7 @@ pub fn diagnostics(&self) -> DiagnosticsConfig {
             },
             insert_use: self.insert_use_config(),
             prefer_no_std: self.data.imports_prefer_no_std,
+            keep_duplicates: false,
         }
     }
 
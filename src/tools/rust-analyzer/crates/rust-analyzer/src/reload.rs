@@ -208,7 +208,9 @@ pub(crate) fn switch_workspaces(&mut self, cause: Cause) {
             self.show_and_log_error("failed to run build scripts".to_string(), Some(error));
         }
 
-        let Some(workspaces) = self.fetch_workspaces_queue.last_op_result() else { return; };
+        let Some(workspaces) = self.fetch_workspaces_queue.last_op_result() else {
+            return;
+        };
         let workspaces =
             workspaces.iter().filter_map(|res| res.as_ref().ok().cloned()).collect::<Vec<_>>();
 
@@ -409,7 +411,9 @@ fn eq_ignore_build_data<'a>(
     fn fetch_workspace_error(&self) -> Result<(), String> {
         let mut buf = String::new();
 
-        let Some(last_op_result) = self.fetch_workspaces_queue.last_op_result() else { return Ok(()) };
+        let Some(last_op_result) = self.fetch_workspaces_queue.last_op_result() else {
+            return Ok(());
+        };
         if last_op_result.is_empty() {
             stdx::format_to!(buf, "rust-analyzer failed to discover workspace");
         } else {
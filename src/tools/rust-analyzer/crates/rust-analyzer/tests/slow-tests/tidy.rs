@@ -250,6 +250,9 @@ fn check_todo(path: &Path, text: &str) {
         "ide-assists/src/tests/generated.rs",
         // The tests for missing fields
         "ide-diagnostics/src/handlers/missing_fields.rs",
+        // `todo!` is a builtin macro, with an expander and inference tests of its own
+        "hir-expand/src/builtin_fn_macro.rs",
+        "hir-ty/src/tests/macros.rs",
     ];
     if need_todo.iter().any(|p| path.ends_with(p)) {
         return;